@@ -0,0 +1,51 @@
+use crate::structures::vec3::Vec3;
+use crate::bsdf::{Bsdf, BsdfSample, flip};
+use crate::bsdf::microfacet::{Ggx, reflect, schlick};
+
+// rough metal, tinted by its reflectance at normal incidence
+#[derive(Debug, Copy, Clone)]
+pub struct Conductor {
+    pub f0: Vec3,
+    pub ggx: Ggx,
+}
+
+impl Conductor {
+    pub fn new(f0: Vec3, roughness: f64) -> Conductor {
+        Conductor { f0, ggx: Ggx::new(roughness) }
+    }
+}
+
+impl Bsdf for Conductor {
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z * wi.z <= 0.0 { return Vec3::new(0.0, 0.0, 0.0); }
+
+        let below = wo.z < 0.0;
+        let (wo, wi) = (flip(wo, below), flip(wi, below));
+        let h = (wo + wi).unit();
+        let f = schlick(self.f0, wo.dot(&h));
+        f * (self.ggx.d(h) * self.ggx.g2(wo, wi) / (4.0 * wo.z * wi.z))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z * wi.z <= 0.0 { return 0.0; }
+
+        let below = wo.z < 0.0;
+        let (wo, wi) = (flip(wo, below), flip(wi, below));
+        let h = (wo + wi).unit();
+        self.ggx.pdf(wo, h) / (4.0 * wo.dot(&h))
+    }
+
+    fn sample(&self, wo: Vec3, _uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z == 0.0 { return None; }
+
+        let below = wo.z < 0.0;
+        let wo = flip(wo, below);
+        let h = self.ggx.sample(wo, u);
+        let wi = reflect(wo, h);
+        if wi.z <= 0.0 { return None; }
+
+        let weight = schlick(self.f0, wo.dot(&h)) * (self.ggx.g2(wo, wi) / self.ggx.g1(wo));
+        let pdf = self.ggx.pdf(wo, h) / (4.0 * wo.dot(&h));
+        Some(BsdfSample { wi: flip(wi, below), weight, pdf, delta: false })
+    }
+}
@@ -0,0 +1,235 @@
+use crate::structures::vec3::Vec3;
+use crate::bsdf::{Bsdf, BsdfSample};
+use crate::bsdf::microfacet::{Ggx, ALPHA_MIN, reflect};
+
+// below this the surface is treated as perfectly smooth
+const SMOOTH_ALPHA: f64 = ALPHA_MIN * 10.0;
+
+// glass-like interface between the outside (+z) and a medium with index ior,
+// rough transmission from Walter et al. (2007),
+// "Microfacet Models for Refraction through Rough Surfaces"
+#[derive(Debug, Copy, Clone)]
+pub struct Dielectric {
+    pub color: Vec3, // tint of transmitted light
+    pub ior: f64,
+    pub ggx: Ggx,
+}
+
+// unpolarized fresnel reflectance, cosine is negative when wo is inside
+pub fn fresnel(cosine: f64, ior: f64) -> f64 {
+    let (cosine, eta) = if cosine < 0.0 { (-cosine, 1.0 / ior) } else { (cosine, ior) };
+
+    let sin2_t = (1.0 - cosine * cosine).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 { return 1.0; } // total internal reflection
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cosine - cos_t) / (eta * cosine + cos_t);
+    let perpendicular = (cosine - eta * cos_t) / (cosine + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) * 0.5
+}
+
+// refract wo through the plane with normal n,
+// eta is the ratio of the index past the plane to the index before it
+pub fn refract(wo: Vec3, n: Vec3, eta: f64) -> Option<Vec3> {
+    let (cosine, n, eta) = match wo.dot(&n) {
+        c if c < 0.0 => (-c, -n, 1.0 / eta),
+        c => (c, n, eta),
+    };
+
+    let sin2_t = (1.0 - cosine * cosine).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 { return None; }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + n * (cosine / eta - cos_t))
+}
+
+impl Dielectric {
+    pub fn new(color: Vec3, ior: f64, roughness: f64) -> Dielectric {
+        Dielectric { color, ior, ggx: Ggx::new(roughness) }
+    }
+
+    fn smooth(&self) -> bool {
+        self.ggx.alpha < SMOOTH_ALPHA
+    }
+
+    // relative index seen by light traveling along wo
+    fn eta(&self, wo: Vec3) -> f64 {
+        if wo.z > 0.0 { self.ior } else { 1.0 / self.ior }
+    }
+
+    // generalized half vector, facing outwards,
+    // None if the pair of directions can't come from a single microfacet
+    fn half(&self, wo: Vec3, wi: Vec3) -> Option<(Vec3, bool)> {
+        if wo.z == 0.0 || wi.z == 0.0 { return None; }
+
+        let reflected = wo.z * wi.z > 0.0;
+        let eta = if reflected { 1.0 } else { self.eta(wo) };
+        let h = wi * eta + wo;
+        if h.length_squared() == 0.0 { return None; }
+
+        let h = h.unit();
+        let h = if h.z < 0.0 { -h } else { h };
+
+        // discard back-facing microfacets
+        if h.dot(&wi) * wi.z < 0.0 || h.dot(&wo) * wo.z < 0.0 { return None; }
+        Some((h, reflected))
+    }
+
+    // visible normal density, from whichever side wo is on
+    fn normal_pdf(&self, wo: Vec3, h: Vec3) -> f64 {
+        let up = if wo.z < 0.0 { -wo } else { wo };
+        self.ggx.pdf(up, h)
+    }
+
+    fn sample_smooth(&self, wo: Vec3, uc: f64) -> Option<BsdfSample> {
+        let f = fresnel(wo.z, self.ior);
+
+        if uc < f {
+            let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+            return Some(BsdfSample { wi, weight: Vec3::new(1.0, 1.0, 1.0), pdf: f, delta: true });
+        }
+
+        let eta = self.eta(wo);
+        let wi = refract(wo, Vec3::new(0.0, 0.0, 1.0), self.ior)?;
+        // radiance is compressed into a smaller solid angle
+        let weight = self.color / (eta * eta);
+        Some(BsdfSample { wi, weight, pdf: 1.0 - f, delta: true })
+    }
+}
+
+impl Bsdf for Dielectric {
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if self.smooth() { return Vec3::new(0.0, 0.0, 0.0); }
+
+        let (h, reflected) = match self.half(wo, wi) {
+            Some(v) => v,
+            None => return Vec3::new(0.0, 0.0, 0.0),
+        };
+
+        let f = fresnel(wo.dot(&h), self.ior);
+        let dg = self.ggx.d(h) * self.ggx.g2(wo, wi);
+
+        if reflected {
+            let value = dg * f / (4.0 * wo.z * wi.z).abs();
+            return Vec3::new(value, value, value);
+        }
+
+        let eta = self.eta(wo);
+        let denom = wi.dot(&h) + wo.dot(&h) / eta;
+        let value = dg * (1.0 - f)
+            * (wi.dot(&h) * wo.dot(&h) / (wi.z * wo.z * denom * denom)).abs();
+        self.color * (value / (eta * eta))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if self.smooth() { return 0.0; }
+
+        let (h, reflected) = match self.half(wo, wi) {
+            Some(v) => v,
+            None => return 0.0,
+        };
+
+        let f = fresnel(wo.dot(&h), self.ior);
+        let normal = self.normal_pdf(wo, h);
+
+        if reflected {
+            return normal / (4.0 * wo.dot(&h).abs()) * f;
+        }
+
+        let eta = self.eta(wo);
+        let denom = wi.dot(&h) + wo.dot(&h) / eta;
+        normal * (wi.dot(&h).abs() / (denom * denom)) * (1.0 - f)
+    }
+
+    fn sample(&self, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z == 0.0 { return None; }
+        if self.smooth() { return self.sample_smooth(wo, uc); }
+
+        let up = if wo.z < 0.0 { -wo } else { wo };
+        let h = self.ggx.sample(up, u);
+        let f = fresnel(wo.dot(&h), self.ior);
+
+        let wi = if uc < f {
+            let wi = reflect(wo, h);
+            if wi.z * wo.z <= 0.0 { return None; }
+            wi
+        } else {
+            let wi = refract(wo, h, self.ior)?;
+            if wi.z * wo.z >= 0.0 || wi.z == 0.0 { return None; }
+            wi
+        };
+
+        let pdf = self.pdf(wo, wi);
+        if pdf <= 0.0 { return None; }
+
+        let weight = self.eval(wo, wi) * (wi.z.abs() / pdf);
+        Some(BsdfSample { wi, weight, pdf, delta: false })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::{Dielectric, fresnel};
+    use crate::bsdf::Bsdf;
+    use crate::structures::vec3::Vec3;
+
+    fn direction(cosine: f64) -> Vec3 {
+        Vec3::new((1.0 - cosine * cosine).sqrt(), 0.0, cosine)
+    }
+
+    #[test]
+    fn test_fresnel() {
+        // ~4% at normal incidence for common glass
+        assert!((fresnel(1.0, 1.5) - 0.04).abs() < 1e-9);
+        // total internal reflection past the critical angle
+        assert_eq!(fresnel(-0.1, 1.5), 1.0);
+    }
+
+    #[test]
+    fn test_white_furnace() {
+        let mut rng = StdRng::seed_from_u64(29);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+
+        for &roughness in [0.0, 0.2, 0.7].iter() {
+            let bsdf = Dielectric::new(white, 1.5, roughness);
+
+            for &cosine in [0.1, 0.5, 1.0].iter() {
+                let mut energy = 0.0;
+                let samples = 20000;
+
+                for _ in 0..samples {
+                    if let Some(sample) = bsdf.sample(direction(cosine), rng.gen(), (rng.gen(), rng.gen())) {
+                        // undo the radiance scaling to count flux
+                        let scale = if sample.wi.z < 0.0 { 1.5 * 1.5 } else { 1.0 };
+                        energy += sample.weight.x * scale;
+                    }
+                }
+
+                let energy = energy / samples as f64;
+                assert!(energy <= 1.01, "{} energy at roughness {}, cos {}", energy, roughness, cosine);
+            }
+        }
+    }
+
+    #[test]
+    fn test_weight_matches_eval() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let bsdf = Dielectric::new(Vec3::new(1.0, 1.0, 1.0), 1.5, 0.4);
+
+        // from both sides of the interface
+        for &wo in [direction(0.6), -direction(0.8)].iter() {
+            for _ in 0..100 {
+                let sample = match bsdf.sample(wo, rng.gen(), (rng.gen(), rng.gen())) {
+                    Some(s) => s,
+                    None => continue,
+                };
+
+                let expected = bsdf.eval(wo, sample.wi) * sample.wi.z.abs() / bsdf.pdf(wo, sample.wi);
+                assert!((sample.weight - expected).length() < 1e-6);
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::f64::consts::PI;
+
+use crate::structures::vec3::Vec3;
+use crate::bsdf::{Bsdf, BsdfSample, sample_cosine, flip};
+
+// lambertian reflection
+#[derive(Debug, Copy, Clone)]
+pub struct Diffuse {
+    pub color: Vec3,
+}
+
+impl Diffuse {
+    pub fn new(color: Vec3) -> Diffuse {
+        Diffuse { color }
+    }
+}
+
+impl Bsdf for Diffuse {
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z * wi.z <= 0.0 { return Vec3::new(0.0, 0.0, 0.0); }
+        self.color / PI
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z * wi.z <= 0.0 { return 0.0; }
+        wi.z.abs() / PI
+    }
+
+    fn sample(&self, wo: Vec3, _uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z == 0.0 { return None; }
+
+        let wi = flip(sample_cosine(u), wo.z < 0.0);
+        Some(BsdfSample { wi, weight: self.color, pdf: wi.z.abs() / PI, delta: false })
+    }
+}
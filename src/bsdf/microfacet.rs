@@ -0,0 +1,96 @@
+use std::f64::consts::PI;
+
+use crate::structures::vec3::Vec3;
+
+// smallest usable alpha, perfectly smooth surfaces
+// make the distribution a dirac delta
+pub const ALPHA_MIN: f64 = 0.0001;
+
+// GGX (Trowbridge-Reitz) microfacet distribution,
+// with Smith height-correlated masking-shadowing.
+// all directions are in the local shading frame (normal is +z).
+#[derive(Debug, Copy, Clone)]
+pub struct Ggx {
+    pub alpha: f64,
+}
+
+impl Ggx {
+    // roughness is perceptual, so it is squared
+    pub fn new(roughness: f64) -> Ggx {
+        Ggx { alpha: (roughness * roughness).max(ALPHA_MIN) }
+    }
+
+    // distribution of normals
+    pub fn d(&self, h: Vec3) -> f64 {
+        if h.z <= 0.0 { return 0.0; }
+
+        let a2 = self.alpha * self.alpha;
+        let denom = h.z * h.z * (a2 - 1.0) + 1.0;
+        a2 / (PI * denom * denom)
+    }
+
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 { return f64::INFINITY; }
+
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) * 0.5
+    }
+
+    // masking
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // masking-shadowing
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // sample a visible normal as seen from wo,
+    // from Heitz (2018), "Sampling the GGX Distribution of Visible Normals"
+    pub fn sample(&self, wo: Vec3, u: (f64, f64)) -> Vec3 {
+        // stretch view into the hemisphere configuration
+        let vh = Vec3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).unit();
+
+        // orthonormal basis around the view
+        let lensq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if lensq > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / lensq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // uniform point on the projected, visible half disk
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        // reproject onto the hemisphere and unstretch
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        Vec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(0.0)).unit()
+    }
+
+    // density of the normals returned by sample
+    pub fn pdf(&self, wo: Vec3, h: Vec3) -> f64 {
+        if wo.z <= 0.0 { return 0.0; }
+        self.g1(wo) * wo.dot(&h).max(0.0) * self.d(h) / wo.z
+    }
+}
+
+// reflect wo about the microfacet normal h
+pub fn reflect(wo: Vec3, h: Vec3) -> Vec3 {
+    2.0 * wo.dot(&h) * h - wo
+}
+
+// the angular part of Schlick's fresnel approximation
+pub fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
+
+pub fn schlick(f0: Vec3, cosine: f64) -> Vec3 {
+    f0 + (1.0 - f0) * schlick_weight(cosine)
+}
@@ -0,0 +1,48 @@
+use crate::structures::vec3::Vec3;
+use crate::bsdf::{Bsdf, BsdfSample};
+
+// linear blend between two bsdfs, 0 is all a, 1 is all b
+pub struct Mix {
+    pub a: Box<dyn Bsdf>,
+    pub b: Box<dyn Bsdf>,
+    pub t: f64,
+}
+
+impl Mix {
+    pub fn new(a: Box<dyn Bsdf>, b: Box<dyn Bsdf>, t: f64) -> Mix {
+        Mix { a, b, t: t.clamp(0.0, 1.0) }
+    }
+}
+
+impl Bsdf for Mix {
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.a.eval(wo, wi) * (1.0 - self.t) + self.b.eval(wo, wi) * self.t
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        self.a.pdf(wo, wi) * (1.0 - self.t) + self.b.pdf(wo, wi) * self.t
+    }
+
+    fn sample(&self, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        // pick a side, then stretch uc back to [0, 1) for the chosen bsdf
+        let (chosen, probability, uc) = if uc < self.t {
+            (&self.b, self.t, uc / self.t)
+        } else {
+            (&self.a, 1.0 - self.t, (uc - self.t) / (1.0 - self.t))
+        };
+
+        let mut sample = chosen.sample(wo, uc, u)?;
+
+        // a delta lobe can't be evaluated by the other side,
+        // so the sample stands on its own
+        if sample.delta {
+            sample.pdf *= probability;
+            return Some(sample);
+        }
+
+        sample.pdf = self.pdf(wo, sample.wi);
+        if sample.pdf <= 0.0 { return None; }
+        sample.weight = self.eval(wo, sample.wi) * (sample.wi.z.abs() / sample.pdf);
+        Some(sample)
+    }
+}
@@ -0,0 +1,43 @@
+use std::f64::consts::PI;
+
+use crate::structures::vec3::Vec3;
+
+pub mod microfacet;
+pub mod principled;
+pub mod diffuse;
+pub mod conductor;
+pub mod dielectric;
+pub mod mix;
+
+// a scattered direction drawn from a bsdf
+#[derive(Debug, Copy, Clone)]
+pub struct BsdfSample {
+    pub wi: Vec3, // incoming direction, in the local frame
+    pub weight: Vec3, // eval * |cos| / pdf
+    pub pdf: f64,
+    pub delta: bool, // drawn from a perfectly specular lobe, eval and pdf are 0
+}
+
+// scattering at a single point on a surface.
+// directions are in the local shading frame, where +z is the outward normal.
+// wo points away from the surface towards the viewer,
+// wi points away from the surface towards the light.
+pub trait Bsdf {
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3;
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64;
+    // uc picks between lobes, u picks a direction in the lobe
+    fn sample(&self, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<BsdfSample>;
+}
+
+// cosine-weighted direction on the local hemisphere (normal is +z)
+pub fn sample_cosine(u: (f64, f64)) -> Vec3 {
+    let r = u.0.sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u.0).max(0.0).sqrt())
+}
+
+// opaque surfaces are two-sided,
+// so mirror directions below the surface into the upper hemisphere
+pub fn flip(w: Vec3, below: bool) -> Vec3 {
+    if below { Vec3::new(w.x, w.y, -w.z) } else { w }
+}
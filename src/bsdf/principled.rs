@@ -0,0 +1,217 @@
+use std::f64::consts::PI;
+
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::bsdf::{Bsdf, BsdfSample, sample_cosine, flip};
+use crate::bsdf::microfacet::{Ggx, reflect, schlick, schlick_weight};
+
+// the reflectance of a dielectric at normal incidence
+// is at most ~8%, specular is a percentage of that.
+pub const DIELECTRIC_F0: f64 = 0.08;
+
+// energy-conserving Lambertian base under a GGX specular layer.
+// metals have no diffuse base and tint their specular layer.
+// all directions are in the local shading frame (normal is +z),
+// wo points away from the surface, towards the viewer.
+#[derive(Debug, Copy, Clone)]
+pub struct Principled {
+    pub diffuse: Vec3, // albedo of the diffuse base
+    pub f0: Vec3, // reflectance of the specular layer at normal incidence
+    pub dielectric: f64, // f0 of the dielectric coating over the base
+    pub ggx: Ggx,
+}
+
+impl Principled {
+    pub fn new(material: &Material) -> Principled {
+        let dielectric = DIELECTRIC_F0 * material.specular;
+        let metallic = material.metallic;
+
+        Principled {
+            diffuse: material.color * (1.0 - metallic),
+            f0: Vec3::new(dielectric, dielectric, dielectric) * (1.0 - metallic)
+                + material.color * metallic,
+            dielectric,
+            ggx: Ggx::new(material.roughness),
+        }
+    }
+
+    fn coating(&self, cosine: f64) -> f64 {
+        self.dielectric + (1.0 - self.dielectric) * schlick_weight(cosine)
+    }
+
+    // light not reflected by the coating, on the way in and on the way out
+    fn transmitted(&self, wo: Vec3, wi: Vec3) -> f64 {
+        (1.0 - self.coating(wo.z)) * (1.0 - self.coating(wi.z))
+    }
+
+    // chance of sampling the specular layer, by roughly how much each lobe reflects
+    fn specular_probability(&self, wo: Vec3) -> f64 {
+        let specular = schlick(self.f0, wo.z).total();
+        let diffuse = self.diffuse.total() * (1.0 - self.coating(wo.z));
+        if specular + diffuse <= 0.0 { return 1.0; }
+        specular / (specular + diffuse)
+    }
+
+    pub fn eval_diffuse(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z <= 0.0 || wi.z <= 0.0 { return Vec3::new(0.0, 0.0, 0.0); }
+        self.diffuse * (self.transmitted(wo, wi) / PI)
+    }
+
+    pub fn eval_specular(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z <= 0.0 || wi.z <= 0.0 { return Vec3::new(0.0, 0.0, 0.0); }
+
+        let h = (wo + wi).unit();
+        let f = schlick(self.f0, wo.dot(&h));
+        f * (self.ggx.d(h) * self.ggx.g2(wo, wi) / (4.0 * wo.z * wi.z))
+    }
+
+    pub fn pdf_diffuse(&self, wi: Vec3) -> f64 {
+        wi.z.max(0.0) / PI
+    }
+
+    pub fn pdf_specular(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let h = (wo + wi).unit();
+        let cosine = wo.dot(&h);
+        if cosine <= 0.0 { return 0.0; }
+        self.ggx.pdf(wo, h) / (4.0 * cosine)
+    }
+}
+
+impl Bsdf for Principled {
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z * wi.z <= 0.0 { return Vec3::new(0.0, 0.0, 0.0); }
+
+        let below = wo.z < 0.0;
+        let (wo, wi) = (flip(wo, below), flip(wi, below));
+        self.eval_diffuse(wo, wi) + self.eval_specular(wo, wi)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z * wi.z <= 0.0 { return 0.0; }
+
+        let below = wo.z < 0.0;
+        let (wo, wi) = (flip(wo, below), flip(wi, below));
+        let p = self.specular_probability(wo);
+        self.pdf_specular(wo, wi) * p + self.pdf_diffuse(wi) * (1.0 - p)
+    }
+
+    fn sample(&self, wo: Vec3, uc: f64, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z == 0.0 { return None; }
+
+        let below = wo.z < 0.0;
+        let wo = flip(wo, below);
+
+        // pick a lobe
+        let wi = if uc < self.specular_probability(wo) {
+            reflect(wo, self.ggx.sample(wo, u))
+        } else {
+            sample_cosine(u)
+        };
+        if wi.z <= 0.0 { return None; }
+
+        // weigh by the density of both lobes
+        let pdf = self.pdf(wo, wi);
+        if pdf <= 0.0 { return None; }
+
+        let weight = (self.eval_diffuse(wo, wi) + self.eval_specular(wo, wi)) * (wi.z / pdf);
+        Some(BsdfSample { wi: flip(wi, below), weight, pdf, delta: false })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::Principled;
+    use crate::bsdf::Bsdf;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+
+    const SAMPLES: usize = 20000;
+
+    fn white(metallic: f64, roughness: f64) -> Material {
+        Material {
+            color: Vec3::new(1.0, 1.0, 1.0),
+            emission: 0.0,
+            metallic,
+            specular: 1.0,
+            roughness,
+            transmission: 0.0,
+        }
+    }
+
+    fn direction(cosine: f64) -> Vec3 {
+        Vec3::new((1.0 - cosine * cosine).sqrt(), 0.0, cosine)
+    }
+
+    // estimate reflected energy under a uniform white environment
+    fn albedo(bsdf: &dyn Bsdf, wo: Vec3, rng: &mut StdRng) -> f64 {
+        let mut energy = 0.0;
+
+        for _ in 0..SAMPLES {
+            if let Some(sample) = bsdf.sample(wo, rng.gen(), (rng.gen(), rng.gen())) {
+                energy += sample.weight.x;
+            }
+        }
+
+        energy / SAMPLES as f64
+    }
+
+    #[test]
+    fn test_white_furnace() {
+        let mut rng = StdRng::seed_from_u64(26);
+
+        for &metallic in [0.0, 0.5, 1.0].iter() {
+            for &roughness in [0.0, 0.1, 0.3, 0.6, 1.0].iter() {
+                let bsdf = Principled::new(&white(metallic, roughness));
+
+                for &cosine in [0.05, 0.3, 0.7, 1.0].iter() {
+                    let energy = albedo(&bsdf, direction(cosine), &mut rng);
+                    assert!(
+                        energy <= 1.01,
+                        "{} energy at metallic {}, roughness {}, cos {}",
+                        energy, metallic, roughness, cosine,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_smooth_metal_is_lossless() {
+        let mut rng = StdRng::seed_from_u64(27);
+        let bsdf = Principled::new(&white(1.0, 0.0));
+
+        for &cosine in [0.1, 0.5, 1.0].iter() {
+            let energy = albedo(&bsdf, direction(cosine), &mut rng);
+            assert!((energy - 1.0).abs() < 0.01, "{} energy at cos {}", energy, cosine);
+        }
+    }
+
+    #[test]
+    fn test_weight_matches_eval() {
+        let mut rng = StdRng::seed_from_u64(28);
+        let bsdf = Principled::new(&white(0.3, 0.5));
+        let wo = direction(0.6);
+
+        for _ in 0..100 {
+            let sample = match bsdf.sample(wo, rng.gen(), (rng.gen(), rng.gen())) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let expected = bsdf.eval(wo, sample.wi) * sample.wi.z / bsdf.pdf(wo, sample.wi);
+            assert!((sample.weight - expected).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_two_sided() {
+        let bsdf = Principled::new(&white(0.0, 0.5));
+        let (wo, wi) = (direction(0.6), Vec3::new(-0.3, 0.2, 0.9).unit());
+        let below = |w: Vec3| Vec3::new(w.x, w.y, -w.z);
+
+        assert_eq!(bsdf.eval(wo, wi), bsdf.eval(below(wo), below(wi)));
+    }
+}
@@ -0,0 +1,307 @@
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::structures::camera::Camera;
+use crate::structures::scene::Scene;
+use crate::structures::film::{Film, Tile};
+use crate::structures::aov::{Pixel, AOVS};
+use crate::structures::vec3::Vec3;
+use crate::objects::march::{March, Marching};
+use crate::objects::trace::Trace;
+use crate::denoise::Denoiser;
+
+const MAGIC: &[u8] = b"keikan checkpoint 2\n";
+// probe rays cast on a grid this wide to tell scenes apart
+const PROBES: usize = 16;
+// a u32 count, then every channel of every aov
+const PIXEL_BYTES: usize = 4 + AOVS * 3 * 8;
+
+// everything needed to pick a render back up where it stopped
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub fingerprint: u64,
+    pub seed: u64,
+    pub done: Vec<bool>, // which pass and tile pairs have been added to the film
+    pub film: Film,
+}
+
+// 64-bit fnv-1a, which unlike the standard library's hasher gives the same
+// value on every build and platform, as checkpoints and workers rely on.
+// numbers are only fed in as little-endian bytes
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u64(&mut self, number: u64) {
+        self.write(&number.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// identifies the camera and scene a render is for.
+// objects can't be compared directly, so instead a grid of probe rays is cast
+// and what they hit is hashed; a scene that looks the same to every probe passes.
+// the denoiser is left out, as it runs after sampling
+pub fn fingerprint(camera: &Camera, scene: &Scene) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher.write(format!("{:?}", Camera { denoiser: Denoiser::Off, ..*camera }).as_bytes());
+
+    let marching = Marching { footprint: camera.footprint(), ..scene.marching };
+    hasher.write(format!("{:?} {:?}", marching, scene.bg).as_bytes());
+    hasher.write_u64(scene.trace.len() as u64);
+    hasher.write_u64(scene.march.len() as u64);
+
+    for i in 0..(PROBES * PROBES) {
+        let x = (i % PROBES) as f64 + 0.5;
+        let y = (i / PROBES) as f64 + 0.5;
+        let ray = camera.make_ray(x * camera.width() as f64 / PROBES as f64, y * camera.height() as f64 / PROBES as f64);
+
+        let casts = [Trace::hit(&scene.trace, ray), March::hit(&scene.march, ray, &marching)];
+        for cast in casts.iter() {
+            match cast {
+                Some(cast) => {
+                    hasher.write_u64(1);
+                    hasher.write_u64(cast.id as u64);
+                    for number in [cast.distance, cast.normal.x, cast.normal.y, cast.normal.z].iter() {
+                        hasher.write_u64(number.to_bits());
+                    }
+                    hasher.write(format!("{:?}", cast.material).as_bytes());
+                },
+                None => hasher.write_u64(0),
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+impl Checkpoint {
+    pub fn new(camera: &Camera, scene: &Scene, seed: u64, jobs: usize) -> Checkpoint {
+        Checkpoint {
+            fingerprint: fingerprint(camera, scene),
+            seed,
+            done: vec![false; jobs],
+            film: Film::new(camera.width(), camera.height()),
+        }
+    }
+
+    // a finished tile of one pass
+    pub fn add(&mut self, job: usize, tile: Tile, samples: &[Pixel]) {
+        self.film.add(tile, samples);
+        self.done[job] = true;
+    }
+
+    // load a checkpoint, refusing it if it was made for another scene
+    pub fn resume(path: &Path, camera: &Camera, scene: &Scene) -> io::Result<Checkpoint> {
+        let checkpoint = Checkpoint::load(path)?;
+        checkpoint.check(camera, scene)?;
+        Ok(checkpoint)
+    }
+
+    // whether this checkpoint can carry on rendering the camera and scene
+    pub fn check(&self, camera: &Camera, scene: &Scene) -> io::Result<()> {
+        let jobs = camera.tiles().len() * camera.aa;
+        let size = (self.film.width(), self.film.height());
+
+        if self.fingerprint != fingerprint(camera, scene) || self.done.len() != jobs || size != (camera.width(), camera.height()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "checkpoint was made for a different scene or camera",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // written next to path first, then moved over it,
+    // so being killed part way through never loses the last checkpoint.
+    // sums are stored as f64s, so the ids in the id aovs come back exactly
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let partial = path.with_extension("partial");
+        let mut file = BufWriter::new(File::create(&partial)?);
+
+        file.write_all(MAGIC)?;
+        for number in [self.fingerprint, self.seed, self.film.width() as u64, self.film.height() as u64, self.done.len() as u64].iter() {
+            file.write_all(&number.to_le_bytes())?;
+        }
+        file.write_all(&self.done.iter().map(|done| *done as u8).collect::<Vec<u8>>())?;
+
+        for (sum, count) in self.film.sum.data().iter().zip(self.film.count.data().iter()) {
+            file.write_all(&(*count as u32).to_le_bytes())?;
+            for value in sum.values.iter() {
+                for channel in [value.x, value.y, value.z].iter() {
+                    file.write_all(&channel.to_le_bytes())?;
+                }
+            }
+        }
+
+        file.flush()?;
+        drop(file);
+        fs::rename(&partial, path)
+    }
+
+    // a failed save mid-render is worth a warning, not the render
+    pub fn keep(&self, path: &Path) {
+        if let Err(error) = self.save(path) {
+            eprintln!("Could not save checkpoint: {}", error);
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        let mut file = BufReader::new(File::open(path)?);
+        let length = file.get_ref().metadata()?.len();
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if magic != MAGIC { return Err(invalid("not a checkpoint")); }
+
+        let mut number = || -> io::Result<u64> {
+            let mut bytes = [0; 8];
+            file.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let (fingerprint, seed) = (number()?, number()?);
+        let (width, height, jobs) = (number()? as usize, number()? as usize, number()? as usize);
+
+        // the header decides how much is allocated, so it has to agree with the file first
+        let header = (MAGIC.len() + 5 * 8) as u64;
+        let expected = width.checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(PIXEL_BYTES))
+            .and_then(|bytes| bytes.checked_add(jobs))
+            .and_then(|bytes| (bytes as u64).checked_add(header));
+        if expected != Some(length) { return Err(invalid("checkpoint is not the size its header says")); }
+
+        let mut done = vec![0; jobs];
+        file.read_exact(&mut done)?;
+
+        let mut film = Film::new(width, height);
+        let mut bytes = vec![0; PIXEL_BYTES];
+        for (sum, count) in film.sum.data_mut().iter_mut().zip(film.count.data_mut().iter_mut()) {
+            file.read_exact(&mut bytes)?;
+            let channel = |i: usize| {
+                let mut word = [0; 8];
+                word.copy_from_slice(&bytes[(4 + i * 8)..(12 + i * 8)]);
+                f64::from_le_bytes(word)
+            };
+
+            *count = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+            *sum = Pixel::empty();
+            for (aov, value) in sum.values.iter_mut().enumerate() {
+                *value = Vec3::new(channel(aov * 3), channel(aov * 3 + 1), channel(aov * 3 + 2));
+            }
+        }
+
+        Ok(Checkpoint {
+            fingerprint, seed,
+            done: done.into_iter().map(|done| done != 0).collect(),
+            film,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::env;
+    use std::io;
+
+    use std::hash::Hasher;
+
+    use super::{Checkpoint, Fnv, fingerprint, MAGIC};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::camera::Camera;
+    use crate::structures::scene::Scene;
+    use crate::structures::material::Material;
+    use crate::structures::film::Tile;
+    use crate::structures::aov::{Aov, Pixel};
+    use crate::objects::sphere::Sphere;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+            60.0, (8, 5), 2, 1, 1,
+        )
+    }
+
+    fn scene(radius: f64) -> Scene {
+        let mut scene = Scene::empty();
+        let grey = Material::dielectric(Vec3::new(0.5, 0.5, 0.5), 0.0, 1.0);
+        scene.add_march(Box::new(Sphere::new(Vec3::new(0.0, 0.0, -4.0), radius, grey)));
+        scene
+    }
+
+    #[test]
+    fn test_fnv() {
+        // the published test vectors
+        let hash = |bytes: &[u8]| { let mut fnv = Fnv::new(); fnv.write(bytes); fnv.finish() };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let same = fingerprint(&camera(), &scene(1.0));
+        assert_eq!(same, fingerprint(&camera(), &scene(1.0)));
+        assert_ne!(same, fingerprint(&camera(), &scene(1.1)));
+
+        let mut moved = camera();
+        moved.fov = 50.0;
+        assert_ne!(same, fingerprint(&moved, &scene(1.0)));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let path = env::temp_dir().join(format!("keikan-test-{}.checkpoint", std::process::id()));
+        let jobs = camera().tiles().len() * camera().aa;
+        let mut checkpoint = Checkpoint::new(&camera(), &scene(1.0), 7, jobs);
+
+        let mut pixel = Pixel::empty();
+        pixel.set(Aov::Beauty, Vec3::new(0.25, 0.5, 2.0));
+        let (object, material) = (16_777_217.0, u32::MAX as f64);
+        pixel.set(Aov::Object, Vec3::new(object, object, object));
+        pixel.set(Aov::Material, Vec3::new(material, material, material));
+        checkpoint.film.add(Tile { x: 2, y: 1, width: 1, height: 1 }, &[pixel]);
+        checkpoint.done[1] = true;
+        checkpoint.save(&path).unwrap();
+
+        let loaded = Checkpoint::resume(&path, &camera(), &scene(1.0)).unwrap();
+        assert_eq!((loaded.seed, loaded.done.clone()), (7, vec![false, true]));
+        assert_eq!(loaded.film.resolve().get(2, 1).get(Aov::Beauty), Vec3::new(0.25, 0.5, 2.0));
+        assert_eq!(loaded.film.count, checkpoint.film.count);
+
+        // ids too big for an f32 survive exactly
+        let resolved = loaded.film.resolve().get(2, 1);
+        assert_eq!(resolved.get(Aov::Object), Vec3::new(object, object, object));
+        assert_eq!(resolved.get(Aov::Material), Vec3::new(material, material, material));
+
+        assert!(Checkpoint::resume(&path, &camera(), &scene(2.0)).is_err());
+        let mut wider = camera();
+        wider.reso = (16, 5);
+        assert!(loaded.check(&wider, &scene(1.0)).is_err());
+
+        // a header claiming a huge film is refused before anything is allocated
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[MAGIC.len() + 16..MAGIC.len() + 24].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
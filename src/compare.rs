@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use image::hdr::HDRDecoder;
+
+use crate::structures::vec3::Vec3;
+use crate::structures::image::Image;
+use crate::structures::gradient::Gradient;
+
+// keeps relative error finite where the reference is black
+const EPSILON: f64 = 0.01;
+// ssim compares gaussian-weighted windows reaching this many pixels out
+const WINDOW: isize = 5;
+const WINDOW_SIGMA: f64 = 1.5;
+// the heatmap spans relative errors from this, as black, up to 1, as white
+const HEATMAP_FLOOR: f64 = 1e-4;
+
+// how far a render is from a reference render of the same size
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Difference {
+    pub mse: f64, // mean squared error over every channel
+    pub psnr: f64, // in decibels, against the brightest channel of the reference or 1, infinite if equal
+    pub ssim: f64, // structural similarity of the luminance as displayed, 1 if equal
+    pub rel_mse: f64, // squared error relative to the reference, so dark areas count as much as bright ones
+}
+
+// how far a render can be from its reference and still pass.
+// noise alone moves relative mse, so golden renders should use a fixed seed
+#[derive(Debug, Copy, Clone)]
+pub struct Tolerance {
+    pub rel_mse: f64, // at most
+    pub ssim: f64, // at least
+}
+
+impl Tolerance {
+    pub fn new() -> Tolerance {
+        Tolerance { rel_mse: 0.01, ssim: 0.95 }
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Tolerance {
+        Tolerance::new()
+    }
+}
+
+impl Difference {
+    pub fn between(image: &Image<Vec3>, reference: &Image<Vec3>) -> io::Result<Difference> {
+        if (image.width(), image.height()) != (reference.width(), reference.height()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "can't compare a {}x{} image to a {}x{} one",
+                image.width(), image.height(), reference.width(), reference.height(),
+            )));
+        }
+
+        let channels = (image.data().len() * 3).max(1) as f64;
+        let mut squared = 0.0;
+        let mut relative = 0.0;
+        let mut peak: f64 = 1.0;
+
+        for (a, b) in image.data().iter().zip(reference.data().iter()) {
+            for (a, b) in [(a.x, b.x), (a.y, b.y), (a.z, b.z)].iter() {
+                squared  += (a - b).powi(2);
+                relative += (a - b).powi(2) / (b * b + EPSILON);
+                peak = peak.max(*b);
+            }
+        }
+
+        let mse = squared / channels;
+        Ok(Difference {
+            mse,
+            psnr: if mse == 0.0 { f64::INFINITY } else { 10.0 * (peak * peak / mse).log10() },
+            ssim: ssim(&luminance(image), &luminance(reference)),
+            rel_mse: relative / channels,
+        })
+    }
+
+    // what failed, if anything. nan always fails
+    pub fn failures(&self, tolerance: &Tolerance) -> Vec<String> {
+        let mut failures = vec![];
+        if self.rel_mse.is_nan() || self.rel_mse > tolerance.rel_mse {
+            failures.push(format!("relative mse {:.6} is over {}", self.rel_mse, tolerance.rel_mse));
+        }
+        if self.ssim.is_nan() || self.ssim < tolerance.ssim {
+            failures.push(format!("ssim {:.6} is under {}", self.ssim, tolerance.ssim));
+        }
+        failures
+    }
+
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.failures(tolerance).is_empty()
+    }
+
+    pub fn print(&self) {
+        println!("mse:          {:.6}", self.mse);
+        println!("psnr:         {:.2} dB", self.psnr);
+        println!("ssim:         {:.6}", self.ssim);
+        println!("relative mse: {:.6}", self.rel_mse);
+    }
+}
+
+// .hdr files are read as linear radiance,
+// anything else as 8-bit color from 0 to 1
+pub fn load(path: &Path) -> io::Result<Image<Vec3>> {
+    let invalid = |error: image::ImageError| io::Error::new(io::ErrorKind::InvalidData, error.to_string());
+
+    if path.extension().and_then(|s| s.to_str()) == Some("hdr") {
+        let decoder = HDRDecoder::new(BufReader::new(File::open(path)?)).map_err(invalid)?;
+        let metadata = decoder.metadata();
+        let data = decoder.read_image_hdr().map_err(invalid)?;
+
+        let width = metadata.width as usize;
+        return Ok(Image::from_fn(width, metadata.height as usize, |x, y| {
+            let [r, g, b] = data[y * width + x].0;
+            Vec3::new(r as f64, g as f64, b as f64)
+        }));
+    }
+
+    Ok(Image::from_rgb8(&image::open(path).map_err(invalid)?.to_rgb()))
+}
+
+// where the render differs, by relative error on a log scale:
+// black is the same, through blue and red, to white for errors of 100% or more
+pub fn heatmap(image: &Image<Vec3>, reference: &Image<Vec3>) -> Image<Vec3> {
+    let ramp = Gradient::new(vec![
+        (0.0,  Vec3::new(0.0, 0.0, 0.0)),
+        (0.25, Vec3::new(0.1, 0.1, 0.6)),
+        (0.5,  Vec3::new(0.8, 0.1, 0.2)),
+        (0.75, Vec3::new(1.0, 0.8, 0.1)),
+        (1.0,  Vec3::new(1.0, 1.0, 1.0)),
+    ]);
+    let floor = HEATMAP_FLOOR.log10();
+
+    Image::from_fn(image.width().min(reference.width()), image.height().min(reference.height()), |x, y| {
+        let (a, b) = (image.get(x, y), reference.get(x, y));
+        let error = [(a.x, b.x), (a.y, b.y), (a.z, b.z)].iter()
+            .map(|(a, b)| (a - b).powi(2) / (b * b + EPSILON))
+            .sum::<f64>() / 3.0;
+
+        ramp.at((error.max(HEATMAP_FLOOR).log10() - floor) / -floor)
+    })
+}
+
+// as it would be displayed, clipped at white
+fn luminance(image: &Image<Vec3>) -> Image<f64> {
+    image.map(|color| (0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z).clamp(0.0, 1.0))
+}
+
+// a gaussian blur, split into a horizontal then vertical pass.
+// weights falling off the edge are left out
+fn blur(image: &Image<f64>) -> Image<f64> {
+    let weights: Vec<f64> = (-WINDOW..=WINDOW)
+        .map(|i| (-(i * i) as f64 / (2.0 * WINDOW_SIGMA * WINDOW_SIGMA)).exp())
+        .collect();
+
+    let pass = |image: &Image<f64>, dx: isize, dy: isize| {
+        let (width, height) = (image.width() as isize, image.height() as isize);
+        Image::from_fn(image.width(), image.height(), |x, y| {
+            let (mut sum, mut total) = (0.0, 0.0);
+            for (i, weight) in (-WINDOW..=WINDOW).zip(weights.iter()) {
+                let (qx, qy) = (x as isize + i * dx, y as isize + i * dy);
+                if qx < 0 || qy < 0 || qx >= width || qy >= height { continue; }
+                sum += weight * image.get(qx as usize, qy as usize);
+                total += weight;
+            }
+            sum / total
+        })
+    };
+
+    pass(&pass(image, 1, 0), 0, 1)
+}
+
+// mean structural similarity, from Wang et al. (2004),
+// with the usual constants for values from 0 to 1
+fn ssim(a: &Image<f64>, b: &Image<f64>) -> f64 {
+    if a.is_empty() { return 1.0; }
+    let (c1, c2) = (0.01_f64.powi(2), 0.03_f64.powi(2));
+
+    let product = |x: &Image<f64>, y: &Image<f64>| {
+        Image::from_fn(x.width(), x.height(), |i, j| x.get(i, j) * y.get(i, j))
+    };
+    let (mean_a, mean_b) = (blur(a), blur(b));
+    let (square_a, square_b, cross) = (blur(&product(a, a)), blur(&product(b, b)), blur(&product(a, b)));
+
+    let mut total = 0.0;
+    for (i, (ma, mb)) in mean_a.data().iter().zip(mean_b.data().iter()).enumerate() {
+        let variance_a = square_a.data()[i] - ma * ma;
+        let variance_b = square_b.data()[i] - mb * mb;
+        let covariance = cross.data()[i] - ma * mb;
+
+        total += ((2.0 * ma * mb + c1) * (2.0 * covariance + c2))
+            / ((ma * ma + mb * mb + c1) * (variance_a + variance_b + c2));
+    }
+
+    total / a.data().len() as f64
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::env;
+
+    use super::{Difference, Tolerance, load, heatmap};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::image::Image;
+    use crate::write;
+
+    fn grey(value: f64) -> Image<Vec3> {
+        Image::new(16, 12, Vec3::new(value, value, value))
+    }
+
+    // a smooth gradient with a few hard edges, for ssim to latch onto
+    fn pattern() -> Image<Vec3> {
+        Image::from_fn(32, 24, |x, y| {
+            let value = x as f64 / 32.0 + if (x / 8 + y / 8) % 2 == 0 { 0.5 } else { 0.0 };
+            Vec3::new(value, value * 0.5, 1.0 - value * 0.5)
+        })
+    }
+
+    #[test]
+    fn test_difference() {
+        let same = Difference::between(&pattern(), &pattern()).unwrap();
+        assert_eq!((same.mse, same.psnr, same.rel_mse), (0.0, f64::INFINITY, 0.0));
+        assert!((same.ssim - 1.0).abs() < 1e-9);
+        assert!(same.passes(&Tolerance::new()));
+
+        let off = Difference::between(&grey(0.6), &grey(0.5)).unwrap();
+        assert!((off.mse - 0.01).abs() < 1e-12);
+        assert!((off.psnr - 20.0).abs() < 1e-9);
+        assert!((off.rel_mse - 0.01 / 0.26).abs() < 1e-12);
+        assert_eq!(off.failures(&Tolerance::new()).len(), 1);
+
+        assert!(Difference::between(&grey(0.5), &pattern()).is_err());
+    }
+
+    #[test]
+    fn test_ssim() {
+        // a little noise barely matters, losing the edges does
+        let noisy = pattern().map(|color| color * 1.02);
+        let flat = pattern().map(|_| Vec3::new(0.6, 0.6, 0.6));
+
+        let slight = Difference::between(&noisy, &pattern()).unwrap();
+        let lost = Difference::between(&flat, &pattern()).unwrap();
+        assert!(slight.ssim > 0.99);
+        assert!(lost.ssim < 0.5);
+        assert!(!lost.passes(&Tolerance::new()));
+    }
+
+    #[test]
+    fn test_load() {
+        let base = env::temp_dir().join(format!("keikan-compare-{}", std::process::id()));
+        let (hdr, png) = (base.with_extension("hdr"), base.with_extension("png"));
+        let bright = pattern().map(|color| color * 4.0);
+
+        // hdr keeps what's past white, to within its 8-bit mantissa
+        write::hdr(&bright, &hdr).unwrap();
+        let loaded = load(&hdr).unwrap();
+        assert!(Difference::between(&loaded, &bright).unwrap().rel_mse < 1e-4);
+
+        // png rounds to the nearest 255th
+        let dim = pattern().map(|color| color * 0.6);
+        write::colors(&dim, &png).unwrap();
+        assert!(Difference::between(&load(&png).unwrap(), &dim).unwrap().mse < 1e-4);
+
+        std::fs::remove_file(&hdr).unwrap();
+        std::fs::remove_file(&png).unwrap();
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let mut changed = pattern();
+        changed.set(3, 4, Vec3::new(0.0, 0.0, 0.0));
+        let map = heatmap(&changed, &pattern());
+
+        assert_eq!(map.get(0, 0), Vec3::new(0.0, 0.0, 0.0));
+        assert!(map.get(3, 4).length() > 1.0);
+    }
+}
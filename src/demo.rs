@@ -1,16 +1,54 @@
+use std::sync::Arc;
+
 use crate::structures::material::Material;
 use crate::structures::camera::Camera;
 use crate::structures::scene::Scene;
 use crate::structures::vec3::Vec3;
+use crate::structures::matrix::Matrix;
+use crate::render::Integrator;
 use crate::objects::sphere::Sphere;
 use crate::objects::plane::Plane;
 use crate::objects::mandelbulb::Mandelbulb;
 use crate::objects::triangle::Triangle;
+use crate::objects::combine::{Union, Difference, Blend};
+use crate::objects::cuboid::Cuboid;
+use crate::objects::torus::Torus;
+use crate::objects::capsule::Capsule;
+use crate::objects::cylinder::Cylinder;
+use crate::objects::cone::Cone;
+use crate::objects::ellipsoid::Ellipsoid;
+use crate::objects::prism::Prism;
+use crate::objects::domain::{Repeat, Twist, Bend};
+use crate::objects::surface::{Onion, Displace};
+use crate::objects::trace::Trace;
+use crate::objects::bvh::Bvh;
+use crate::objects::instance::Instance;
+use crate::objects::transform::Transformed;
+use crate::objects::mandelbox::Mandelbox;
+use crate::objects::menger::Menger;
+use crate::objects::sierpinski::Sierpinski;
+use crate::objects::julia::Julia;
+use crate::objects::kifs::Kifs;
+use crate::objects::orbit::{OrbitTrap, Trap};
+use crate::structures::gradient::Gradient;
 
 // const RESOLUTION: (usize, usize) = (1920, 1080);
 // const RESOLUTION: (usize, usize) = (1440, 900);
 const RESOLUTION: (usize, usize) = (720, 450);
 
+pub type Demo = fn() -> (Scene, Camera);
+
+// every demo, by the name it's picked with, e.g. `--demo forest`
+pub const DEMOS: [(&str, Demo); 9] = [
+    ("mandelbulb", mandelbulb), ("specular", specular), ("triangle", triangle),
+    ("materials", materials), ("blobs", blobs), ("primitives", primitives),
+    ("operators", operators), ("forest", forest), ("fractals", fractals),
+];
+
+pub fn named(name: &str) -> Option<Demo> {
+    DEMOS.iter().find(|(demo, _)| *demo == name).map(|(_, demo)| *demo)
+}
+
 pub fn mandelbulb() -> (Scene, Camera) {
     let camera = Camera::new(
         Vec3::new(-2.0, 0.6, 4.0),
@@ -49,14 +87,16 @@ pub fn mandelbulb() -> (Scene, Camera) {
 }
 
 pub fn specular() -> (Scene, Camera) {
-    let camera = Camera::new(
+    let mut camera = Camera::new(
         Vec3::new(5.0, 2.2, 5.0),
         Vec3::new(0.0, 1.2, 0.0),
         Vec3::new(0.0, 1.0, 0.0),
         60.0,
         RESOLUTION,
-        64, 1, 3,
+        64, 1, 12,
     );
+    // mirrors need deep bounces
+    camera.integrator = Integrator::Path { min_depth: 3 };
 
     let mut scene = Scene::empty();
     scene.bg = Material::emissive(Vec3::new(0.5, 0.5, 1.0), 0.2);
@@ -165,3 +205,317 @@ pub fn materials() -> (Scene, Camera) {
 
     return (scene, camera);
 }
+
+pub fn blobs() -> (Scene, Camera) {
+    let camera = Camera::new(
+        Vec3::new(0.0, 2.0, 6.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        RESOLUTION,
+        16, 1, 3,
+    );
+
+    let mut scene = Scene::empty();
+
+    let red    = Material::dielectric(Vec3::new(0.9, 0.2, 0.2), 0.5, 0.3);
+    let yellow = Material::dielectric(Vec3::new(0.9, 0.8, 0.2), 0.5, 0.3);
+    let chrome = Material::metal(Vec3::new(0.9, 0.9, 0.9), 0.1);
+
+    // two spheres melting into each other
+    let blob = Union::new(
+        Box::new(Sphere::new(Vec3::new(-0.6, 1.0, 0.0), 0.8, red)),
+        Box::new(Sphere::new(Vec3::new( 0.6, 1.0, 0.0), 0.6, yellow)),
+        Blend::Polynomial(0.5),
+    );
+
+    // with a bite taken out of the front
+    let bitten = Difference::new(
+        Box::new(blob),
+        Box::new(Sphere::new(Vec3::new(0.0, 1.2, 1.0), 0.5, chrome)),
+        Blend::Polynomial(0.1),
+    );
+
+    let ground = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), chrome);
+
+    scene.add_march(Box::new(bitten));
+    scene.add_trace(Box::new(ground));
+
+    return (scene, camera);
+}
+
+pub fn primitives() -> (Scene, Camera) {
+    let camera = Camera::new(
+        Vec3::new(0.0, 4.0, 12.0),
+        Vec3::new(0.0, 0.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        RESOLUTION,
+        16, 1, 3,
+    );
+
+    let mut scene = Scene::empty();
+
+    let red    = Material::dielectric(Vec3::new(0.9, 0.2, 0.2), 0.5, 0.3);
+    let yellow = Material::dielectric(Vec3::new(0.9, 0.8, 0.2), 0.5, 0.3);
+    let blue   = Material::dielectric(Vec3::new(0.2, 0.4, 0.9), 0.5, 0.3);
+    let gold   = Material::metal(Vec3::new(1.0, 0.8, 0.4), 0.2);
+    let white  = Material::dielectric(Vec3::new(0.8, 0.8, 0.8), 0.0, 1.0);
+
+    // back row
+    scene.add_march(Box::new(Cuboid::new(Vec3::new(-3.0, 0.6, -1.5), Vec3::new(0.6, 0.6, 0.6), red)));
+    scene.add_march(Box::new(Cuboid::rounded(Vec3::new(-1.0, 0.6, -1.5), Vec3::new(0.6, 0.6, 0.6), 0.2, yellow)));
+    scene.add_march(Box::new(Torus::new(Vec3::new(1.0, 0.25, -1.5), 0.5, 0.25, gold)));
+    scene.add_march(Box::new(Capsule::new(Vec3::new(2.6, 0.3, -1.5), Vec3::new(3.4, 1.1, -1.5), 0.3, blue)));
+
+    // front row
+    scene.add_march(Box::new(Cylinder::new(Vec3::new(-3.0, 0.6, 1.0), 0.6, 0.5, blue)));
+    scene.add_march(Box::new(Cone::new(Vec3::new(-1.0, 0.6, 1.0), 0.6, 0.6, 0.0, gold)));
+    scene.add_march(Box::new(Ellipsoid::new(Vec3::new(1.0, 0.5, 1.0), Vec3::new(0.7, 0.5, 0.4), red)));
+    scene.add_march(Box::new(Prism::new(Vec3::new(3.0, 0.5, 1.0), 0.5, 0.5, yellow)));
+
+    scene.add_trace(Box::new(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), white)));
+
+    return (scene, camera);
+}
+
+pub fn operators() -> (Scene, Camera) {
+    let camera = Camera::new(
+        Vec3::new(0.0, 4.0, 12.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        RESOLUTION,
+        16, 1, 3,
+    );
+
+    let mut scene = Scene::empty();
+
+    let red    = Material::dielectric(Vec3::new(0.9, 0.2, 0.2), 0.5, 0.3);
+    let yellow = Material::dielectric(Vec3::new(0.9, 0.8, 0.2), 0.5, 0.3);
+    let blue   = Material::dielectric(Vec3::new(0.2, 0.4, 0.9), 0.5, 0.3);
+    let gold   = Material::metal(Vec3::new(1.0, 0.8, 0.4), 0.2);
+    let white  = Material::dielectric(Vec3::new(0.8, 0.8, 0.8), 0.0, 1.0);
+
+    // a twisted column
+    let column = Cuboid::new(Vec3::new(-3.0, 1.5, 0.0), Vec3::new(0.5, 1.5, 0.5), red);
+    scene.add_march(Box::new(Twist::new(Box::new(column), Vec3::new(-3.0, 0.0, 0.0), 1.0, 0.8)));
+
+    // a bent bar
+    let bar = Cuboid::rounded(Vec3::new(-0.8, 1.0, 0.0), Vec3::new(1.2, 0.25, 0.25), 0.1, yellow);
+    scene.add_march(Box::new(Bend::new(Box::new(bar), Vec3::new(-0.8, 1.0, 0.0), 0.6, 1.5)));
+
+    // a lumpy shell, cut open
+    let shell = Onion::new(Box::new(Sphere::new(Vec3::new(1.5, 1.0, 0.0), 0.9, blue)), 0.05);
+    let lumpy = Displace::new(Box::new(shell), 0.05, 3.0);
+    let cut = Cuboid::new(Vec3::new(1.5, 1.0, 1.0), Vec3::new(1.0, 1.0, 0.8), blue);
+    scene.add_march(Box::new(Difference::new(Box::new(lumpy), Box::new(cut), Blend::Hard)));
+
+    // a row of spheres
+    let ball = Sphere::new(Vec3::new(0.0, 0.3, 2.5), 0.3, gold);
+    let row = Repeat::bounded(Box::new(ball), Vec3::new(1.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0));
+    scene.add_march(Box::new(row));
+
+    scene.add_trace(Box::new(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), white)));
+
+    return (scene, camera);
+}
+
+// a pyramid trunk under a lumpy canopy
+fn tree() -> Bvh {
+    let bark  = Material::dielectric(Vec3::new(0.4, 0.25, 0.1), 0.2, 0.8);
+    let leafy = Material::dielectric(Vec3::new(0.2, 0.6, 0.15), 0.3, 0.6);
+
+    let top = Vec3::new(0.0, 1.6, 0.0);
+    let base = [
+        Vec3::new(-0.15, 0.0, -0.15), Vec3::new( 0.15, 0.0, -0.15),
+        Vec3::new( 0.15, 0.0,  0.15), Vec3::new(-0.15, 0.0,  0.15),
+    ];
+
+    let mut parts: Vec<Box<dyn Trace>> = (0..4)
+        .map(|i| Box::new(Triangle::new(base[i], base[(i + 1) % 4], top, bark)) as Box<dyn Trace>)
+        .collect();
+
+    parts.push(Box::new(Sphere::new(Vec3::new(0.0, 1.3, 0.0), 0.6, leafy)));
+    parts.push(Box::new(Sphere::new(Vec3::new(0.25, 1.7, 0.1), 0.4, leafy)));
+    parts.push(Box::new(Sphere::new(Vec3::new(-0.2, 1.6, -0.2), 0.35, leafy)));
+
+    Bvh::new(parts)
+}
+
+pub fn forest() -> (Scene, Camera) {
+    let camera = Camera::new(
+        Vec3::new(0.0, 10.0, 24.0),
+        Vec3::new(0.0, 0.0, -2.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        RESOLUTION,
+        16, 1, 3,
+    );
+
+    let mut scene = Scene::empty();
+
+    let autumn = Material::dielectric(Vec3::new(0.9, 0.4, 0.1), 0.3, 0.6);
+    let grass  = Material::dielectric(Vec3::new(0.5, 0.6, 0.4), 0.0, 1.0);
+
+    // one tree, planted many times
+    let tree: Arc<dyn Trace> = Arc::new(tree());
+    let mut trees: Vec<Box<dyn Trace>> = vec![];
+
+    for i in 0..16 {
+        for j in 0..16 {
+            let seed = (i * 16 + j) as f64;
+            let position = Vec3::new(i as f64 - 7.5, 0.0, j as f64 - 7.5) * 1.6
+                + Vec3::new((seed * 12.9).sin(), 0.0, (seed * 78.2).sin()) * 0.4;
+            let size = 0.8 + 0.4 * (seed * 4.1).sin().abs();
+
+            let matrix = Matrix::translate(position)
+                * Matrix::rotate(Vec3::new(0.0, 1.0, 0.0), seed * 0.7)
+                * Matrix::scale(Vec3::new(size, size, size));
+            let material = if (i + j) % 5 == 0 { Some(autumn) } else { None };
+
+            trees.push(Box::new(Instance::new(tree.clone(), matrix, material).unwrap()));
+        }
+    }
+
+    scene.add_trace(Box::new(Bvh::new(trees)));
+    scene.add_trace(Box::new(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), grass)));
+
+    return (scene, camera);
+}
+
+pub fn fractals() -> (Scene, Camera) {
+    let camera = Camera::new(
+        Vec3::new(0.0, 4.0, 16.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        RESOLUTION,
+        16, 1, 3,
+    );
+
+    let mut scene = Scene::empty();
+
+    let red    = Material::dielectric(Vec3::new(0.9, 0.2, 0.2), 0.5, 0.3);
+    let yellow = Material::dielectric(Vec3::new(0.9, 0.8, 0.2), 0.5, 0.3);
+    let blue   = Material::dielectric(Vec3::new(0.2, 0.4, 0.9), 0.5, 0.3);
+    let gold   = Material::metal(Vec3::new(1.0, 0.8, 0.4), 0.2);
+    let white  = Material::dielectric(Vec3::new(0.8, 0.8, 0.8), 0.0, 1.0);
+
+    // orange through purple to blue
+    let sunset = Gradient::new(vec![
+        (0.0, Vec3::new(1.0, 0.6, 0.1)),
+        (0.5, Vec3::new(0.6, 0.1, 0.5)),
+        (1.0, Vec3::new(0.1, 0.2, 0.8)),
+    ]);
+
+    // a mandelbox, shrunk to fit
+    let mut mandelbox = Mandelbox::new(Vec3::new(0.0, 0.0, 0.0), -1.5, 12, red);
+    mandelbox.coloring = Some(OrbitTrap { scale: 6.0, ..OrbitTrap::new(Trap::Origin, sunset.clone()) });
+    let shrink = Matrix::translate(Vec3::new(-4.0, 1.0, 0.0)) * Matrix::scale(Vec3::new(0.25, 0.25, 0.25));
+    scene.add_march(Box::new(Transformed::new(Box::new(mandelbox), shrink).unwrap()));
+
+    scene.add_march(Box::new(Menger::new(Vec3::new(-2.0, 1.0, 0.0), 0.8, 4, yellow)));
+    scene.add_march(Box::new(Sierpinski::new(Vec3::new(0.0, 1.0, 0.0), 0.8, 8, blue)));
+    let mut julia = Julia::new(Vec3::new(2.0, 1.0, 0.0), [-0.2, 0.6, 0.2, 0.2], 12, gold);
+    julia.coloring = Some(OrbitTrap::new(Trap::Origin, sunset.clone()));
+    scene.add_march(Box::new(julia));
+
+    let turn = Matrix::rotate(Vec3::new(1.0, 1.0, 0.0), 0.3);
+    let mut kifs = Kifs::new(Vec3::new(0.0, 0.0, 0.0), 2.0, Vec3::new(1.0, 1.0, 1.0), turn, 10, red);
+    kifs.coloring = Some(OrbitTrap::new(Trap::Plane(Vec3::new(0.0, 1.0, 0.0)), sunset));
+    let place = Matrix::translate(Vec3::new(4.0, 1.0, 0.0)) * Matrix::scale(Vec3::new(0.8, 0.8, 0.8));
+    scene.add_march(Box::new(Transformed::new(Box::new(kifs), place).unwrap()));
+
+    scene.add_trace(Box::new(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), white)));
+
+    return (scene, camera);
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::env;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::structures::control::Control;
+    use crate::structures::aov::Aov;
+    use crate::denoise::Denoiser;
+    use crate::compare::{self, Difference, Tolerance};
+    use crate::write;
+
+    // the demos shrunk this many times, to keep the golden images small and quick
+    const SHRINK: usize = 8;
+
+    // how a demo's camera is set up for its golden image
+    type Setup = fn(&mut Camera);
+
+    fn preview(camera: &mut Camera) {
+        camera.integrator = Integrator::Preview;
+        camera.aa = 1;
+    }
+
+    // a few paths per pixel, so the bsdfs are sampled too
+    fn path(camera: &mut Camera) {
+        camera.integrator = Integrator::Path { min_depth: 3 };
+        camera.aa = 4;
+    }
+
+    fn denoised(camera: &mut Camera) {
+        path(camera);
+        camera.denoiser = Denoiser::atrous();
+    }
+
+    // renders each demo with the preview integrator, and one with paths
+    // with and without denoising, all with a fixed seed,
+    // then compares them to golden/<name>.hdr.
+    // after changing how a demo should look, run with KEIKAN_BLESS=1 to save new golden images
+    #[test]
+    fn test_golden() {
+        let mut cases: Vec<(String, Demo, Setup)> = DEMOS.iter()
+            .map(|(name, demo)| (name.to_string(), *demo, preview as Setup))
+            .collect();
+        cases.push(("materials-path".to_string(), materials, path));
+        cases.push(("materials-denoised".to_string(), materials, denoised));
+
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("golden");
+        let bless = env::var_os("KEIKAN_BLESS").is_some();
+        let mut failures = vec![];
+
+        for (name, demo, setup) in cases.iter() {
+            let (scene, mut camera) = demo();
+            camera.reso = (camera.width() / SHRINK, camera.height() / SHRINK);
+            setup(&mut camera);
+
+            let mut control = Control::new();
+            control.seed = 1;
+            let image = camera.render_with(scene, &control).unwrap().0.layer(Aov::Beauty);
+            let path = directory.join(format!("{}.hdr", name));
+
+            if bless {
+                write::hdr(&image, &path).unwrap();
+                continue;
+            }
+
+            let reference = match compare::load(&path) {
+                Ok(reference) => reference,
+                Err(error) => { failures.push(format!("{}: {}", name, error)); continue; },
+            };
+            let difference = match Difference::between(&image, &reference) {
+                Ok(difference) => difference,
+                Err(error) => { failures.push(format!("{}: {}", name, error)); continue; },
+            };
+
+            if !difference.passes(&Tolerance::new()) {
+                let heatmap = env::temp_dir().join(format!("keikan-{}.heatmap.png", name));
+                write::colors(&compare::heatmap(&image, &reference), &heatmap).ok();
+                failures.push(format!(
+                    "{}: {}, see {}",
+                    name, difference.failures(&Tolerance::new()).join(" and "), heatmap.display(),
+                ));
+            }
+        }
+
+        assert!(failures.is_empty(), "demos differ from their golden images:\n{}", failures.join("\n"));
+    }
+}
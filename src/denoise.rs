@@ -0,0 +1,173 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::aov::{Aov, Pixel};
+use crate::structures::image::Image;
+
+// B3 spline, the wavelet used at every scale
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// how strongly edges in each feature buffer stop the blur
+const SIGMA_NORMAL: f64 = 0.3;
+const SIGMA_ALBEDO: f64 = 0.1;
+const SIGMA_DEPTH:  f64 = 0.05; // relative to the depth, per pixel of filter step
+
+// how the beauty pass is cleaned up once rendering is done
+#[derive(Debug, Copy, Clone)]
+pub enum Denoiser {
+    Off,
+    // edge-avoiding à-trous wavelet filter from Dammertz et al. (2010),
+    // guided by the normal, albedo and depth aovs.
+    // each iteration doubles the filter radius,
+    // sigma is how different two colors can be and still be blurred together.
+    ATrous { iterations: usize, sigma: f64 },
+}
+
+impl Denoiser {
+    // a good starting point for previews
+    pub fn atrous() -> Denoiser {
+        Denoiser::ATrous { iterations: 5, sigma: 0.5 }
+    }
+
+    pub fn apply(&self, image: Image<Pixel>) -> Image<Pixel> {
+        match *self {
+            Denoiser::Off => image,
+            Denoiser::ATrous { iterations, sigma } => atrous(image, iterations, sigma),
+        }
+    }
+}
+
+// compress hdr colors so fireflies don't dominate the edge-stopping
+fn compress(color: Vec3) -> Vec3 {
+    color / (color + 1.0)
+}
+
+fn atrous(mut image: Image<Pixel>, iterations: usize, sigma: f64) -> Image<Pixel> {
+    let (width, height) = (image.width() as isize, image.height() as isize);
+    // once the step is as wide as the image, every neighbour falls off the edge
+    let useful = (usize::BITS - image.width().max(image.height()).leading_zeros()) as usize;
+
+    for iteration in 0..iterations.min(useful) {
+        let step: isize = 1 << iteration;
+        // finer scales hold more noise, so be more forgiving there
+        let sigma_color = sigma / step as f64;
+        let mut filtered = image.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let center = image.get(x as usize, y as usize);
+                let color  = compress(center.get(Aov::Beauty));
+                let normal = center.get(Aov::Normal);
+                let albedo = center.get(Aov::Albedo);
+                let depth  = center.get(Aov::Depth).x;
+
+                let mut sum = Vec3::new(0.0, 0.0, 0.0);
+                let mut total = 0.0;
+
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx = x + (i as isize - 2) * step;
+                        let qy = y + (j as isize - 2) * step;
+                        if qx < 0 || qy < 0 || qx >= width || qy >= height { continue; }
+
+                        let other = image.get(qx as usize, qy as usize);
+                        let d_color  = (compress(other.get(Aov::Beauty)) - color).length_squared();
+                        let d_normal = (other.get(Aov::Normal) - normal).length_squared();
+                        let d_albedo = (other.get(Aov::Albedo) - albedo).length_squared();
+                        let d_depth  = (other.get(Aov::Depth).x - depth).abs()
+                            / (SIGMA_DEPTH * depth.max(other.get(Aov::Depth).x).max(1.0) * step as f64);
+
+                        let weight = kx * ky
+                            * (-d_color / (sigma_color * sigma_color)).exp()
+                            * (-d_normal / (SIGMA_NORMAL * SIGMA_NORMAL)).exp()
+                            * (-d_albedo / (SIGMA_ALBEDO * SIGMA_ALBEDO)).exp()
+                            * (-d_depth).exp();
+
+                        sum = sum + other.get(Aov::Beauty) * weight;
+                        total += weight;
+                    }
+                }
+
+                // the center always has full weight, so total is never 0
+                let mut pixel = center;
+                pixel.set(Aov::Beauty, sum / total);
+                filtered.set(x as usize, y as usize, pixel);
+            }
+        }
+
+        image = filtered;
+    }
+
+    image
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::Denoiser;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::aov::{Aov, Pixel};
+    use crate::structures::image::Image;
+
+    const SIZE: usize = 32;
+
+    // noisy grey on the left half, noisy white on the right,
+    // with matching albedo and a flat floor facing the camera
+    fn noisy(rng: &mut StdRng) -> Image<Pixel> {
+        Image::from_fn(SIZE, SIZE, |x, _| {
+            let base = if x < SIZE / 2 { 0.2 } else { 1.0 };
+            let noise = base * (rng.gen::<f64>() - 0.5);
+
+            let mut pixel = Pixel::empty();
+            pixel.set(Aov::Beauty, Vec3::new(base + noise, base + noise, base + noise));
+            pixel.set(Aov::Albedo, Vec3::new(base, base, base));
+            pixel.set(Aov::Normal, Vec3::new(0.0, 0.0, 1.0));
+            pixel.set(Aov::Depth, Vec3::new(1.0, 1.0, 1.0));
+            pixel
+        })
+    }
+
+    // squared error against the noise-free image
+    fn error(image: &Image<Pixel>) -> f64 {
+        let mut error = 0.0;
+
+        for row in image.rows() {
+            for (x, pixel) in row.iter().enumerate() {
+                let base = if x < SIZE / 2 { 0.2 } else { 1.0 };
+                error += (pixel.get(Aov::Beauty).x - base).powi(2);
+            }
+        }
+
+        error
+    }
+
+    #[test]
+    fn test_reduces_noise() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let image = noisy(&mut rng);
+        let before = error(&image);
+        let after = error(&Denoiser::atrous().apply(image));
+
+        assert!(after < before * 0.25, "error went from {} to {}", before, after);
+    }
+
+    #[test]
+    fn test_keeps_edges() {
+        let mut rng = StdRng::seed_from_u64(31);
+        let image = Denoiser::atrous().apply(noisy(&mut rng));
+
+        // the albedo edge keeps the halves from bleeding into each other
+        let row = image.row(SIZE / 2);
+        assert!(row[SIZE / 2 - 1].get(Aov::Beauty).x < 0.3);
+        assert!(row[SIZE / 2].get(Aov::Beauty).x > 0.9);
+    }
+
+    #[test]
+    fn test_many_iterations() {
+        // steps wider than the image change nothing, however many are asked for
+        let mut rng = StdRng::seed_from_u64(32);
+        let image = noisy(&mut rng);
+        let most = Denoiser::ATrous { iterations: 6, sigma: 0.5 }.apply(image.clone()).layer(Aov::Beauty);
+        assert_eq!(Denoiser::ATrous { iterations: 100, sigma: 0.5 }.apply(image).layer(Aov::Beauty), most);
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write, BufRead, BufReader, BufWriter};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::structures::vec3::Vec3;
+use crate::structures::camera::Camera;
+use crate::structures::scene::Scene;
+use crate::structures::aov::Pixel;
+use crate::structures::control::{Control, Cancel, Progress};
+use crate::structures::stats::Stats;
+use crate::structures::image::Image;
+use crate::structures::film::Tile;
+use crate::checkpoint::{Checkpoint, fingerprint};
+
+// a render split between processes, possibly on other machines.
+// the coordinator hands out jobs, one pass over one tile each, to whichever
+// workers connect; a job whose worker disappears is handed to another.
+// workers build the scene themselves, so they must run the same build
+// with the same flags; a worker whose scene differs is turned away.
+//
+// messages are a tag byte followed by little-endian numbers:
+//   worker hello:       fingerprint
+//   coordinator job:    job, seed
+//   worker result:      job, then every aov of every pixel in the tile as f64s
+//   coordinator done or reject, with nothing after
+
+const HELLO: u8 = 1;
+const JOB: u8 = 2;
+const RESULT: u8 = 3;
+const DONE: u8 = 4;
+const REJECT: u8 = 5;
+
+// how long to wait on a worker before giving its job to someone else
+pub const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// how often to check for new workers, or for jobs coming free
+const POLL: Duration = Duration::from_millis(20);
+
+fn send(stream: &mut impl Write, tag: u8, numbers: &[u64]) -> io::Result<()> {
+    stream.write_all(&[tag])?;
+    for number in numbers.iter() {
+        stream.write_all(&number.to_le_bytes())?;
+    }
+    stream.flush()
+}
+
+fn tag(stream: &mut impl Read) -> io::Result<u8> {
+    let mut tag = [0];
+    stream.read_exact(&mut tag)?;
+    Ok(tag[0])
+}
+
+fn number(stream: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    stream.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn unexpected(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
+
+// render jobs for a coordinator until it says it's done,
+// returning how many were rendered here
+pub fn work(address: impl ToSocketAddrs, camera: Camera, mut scene: Scene) -> io::Result<usize> {
+    scene.prepare(&camera);
+    let tiles = camera.tiles();
+    let cancel = Cancel::new();
+
+    let stream = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    send(&mut writer, HELLO, &[fingerprint(&camera, &scene)])?;
+
+    let mut rendered = 0;
+    loop {
+        match tag(&mut reader)? {
+            JOB => {
+                let (job, seed) = (number(&mut reader)? as usize, number(&mut reader)?);
+                if job >= tiles.len() * camera.aa { return Err(unexpected("job out of range")); }
+
+                let samples = camera.job(&scene, &tiles, job, seed, &cancel).expect("never cancelled");
+                let values = samples.data().iter()
+                    .flat_map(|pixel| pixel.values.iter())
+                    .flat_map(|value| [value.x, value.y, value.z])
+                    .map(f64::to_bits);
+
+                send(&mut writer, RESULT, &std::iter::once(job as u64).chain(values).collect::<Vec<u64>>())?;
+                rendered += 1;
+            },
+            DONE => return Ok(rendered),
+            REJECT => return Err(unexpected("the coordinator is rendering a different scene or camera")),
+            _ => return Err(unexpected("unknown message")),
+        }
+    }
+}
+
+// everything the coordinator's connections share
+struct Shared<'a> {
+    camera: Camera,
+    tiles: Vec<Tile>,
+    control: &'a Control,
+    fingerprint: u64,
+    queue: Mutex<VecDeque<usize>>, // jobs waiting for a worker
+    remaining: AtomicUsize, // jobs not yet back from a worker
+    taken: AtomicUsize, // samples promised to jobs, for the budget
+    state: Mutex<Checkpoint>,
+    saved: Mutex<Instant>,
+    start: Instant,
+}
+
+impl Shared<'_> {
+    fn over(&self) -> bool {
+        self.remaining.load(Ordering::Relaxed) == 0 || self.control.stopped(self.start.elapsed())
+    }
+
+    fn area(&self, job: usize) -> usize {
+        self.tiles[job % self.tiles.len()].area()
+    }
+
+    // wait for a job, or None once there's nothing left to do.
+    // once the next job would go over the sample budget, the rest are dropped
+    // and the render is over when the jobs already out come back
+    fn next(&self) -> Option<usize> {
+        let budget = self.control.samples.unwrap_or(usize::MAX);
+
+        while !self.over() {
+            let mut queue = self.queue.lock().expect("a connection panicked");
+            if let Some(job) = queue.pop_front() {
+                let area = self.area(job);
+                if self.taken.fetch_add(area, Ordering::Relaxed) + area <= budget { return Some(job); }
+
+                self.taken.fetch_sub(area, Ordering::Relaxed);
+                self.remaining.fetch_sub(queue.len() + 1, Ordering::Relaxed);
+                queue.clear();
+                continue;
+            }
+            drop(queue);
+            thread::sleep(POLL);
+        }
+        None
+    }
+
+    // give a job back for someone else to do
+    fn requeue(&self, job: usize) {
+        self.taken.fetch_sub(self.area(job), Ordering::Relaxed);
+        self.queue.lock().expect("a connection panicked").push_front(job);
+    }
+
+    // only one connection saves at a time, the rest carry on
+    fn keep(&self) {
+        if let (Some((path, every)), Ok(mut last)) = (&self.control.checkpoint, self.saved.try_lock()) {
+            if last.elapsed() >= *every {
+                self.state.lock().expect("a connection panicked").keep(path);
+                *last = Instant::now();
+            }
+        }
+    }
+
+    fn progress(&self, finished: bool) -> Progress {
+        let state = self.state.lock().expect("a connection panicked");
+        let jobs = state.done.len();
+        Progress {
+            tiles: state.done.iter().filter(|done| **done).count(),
+            total_tiles: jobs,
+            samples: state.film.count.data().iter().sum(),
+            total_samples: self.camera.rendered().area() * self.camera.aa,
+            elapsed: self.start.elapsed(),
+            finished,
+        }
+    }
+}
+
+// talk to one worker until the render is over or the worker goes away
+fn connection(stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    // a connection that never says hello doesn't hold up the render
+    wait(&mut reader, shared)?;
+    if tag(&mut reader)? != HELLO { return Err(unexpected("expected hello")); }
+    if number(&mut reader)? != shared.fingerprint {
+        send(&mut writer, REJECT, &[])?;
+        return Err(unexpected("worker is rendering a different scene or camera"));
+    }
+
+    while let Some(job) = shared.next() {
+        let result = send(&mut writer, JOB, &[job as u64, shared.control.seed])
+            .and_then(|_| wait(&mut reader, shared))
+            .and_then(|_| receive(&mut reader, job, shared.area(job)));
+
+        let samples = match result {
+            Ok(samples) => samples,
+            // the render stopped first, so the job isn't needed
+            Err(_) if shared.over() => break,
+            Err(error) => {
+                // someone else will have to do it
+                shared.requeue(job);
+                return Err(error);
+            },
+        };
+
+        shared.state.lock().expect("a connection panicked").add(job, shared.tiles[job % shared.tiles.len()], &samples);
+        shared.remaining.fetch_sub(1, Ordering::Relaxed);
+        shared.control.report(shared.progress(false));
+        shared.keep();
+    }
+
+    send(&mut writer, DONE, &[])
+}
+
+// wait for a worker to start sending its next message, checking every so often
+// whether the render is over meanwhile
+fn wait(reader: &mut BufReader<TcpStream>, shared: &Shared) -> io::Result<()> {
+    let waiting = Instant::now();
+    reader.get_ref().set_read_timeout(Some(POLL))?;
+
+    let answered = loop {
+        match reader.fill_buf() {
+            Ok(_) => break Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut => {
+                if shared.over() { break Err(io::Error::new(io::ErrorKind::Interrupted, "the render is over")); }
+                if waiting.elapsed() >= TIMEOUT { break Err(error); }
+            },
+            Err(error) => break Err(error),
+        }
+    };
+
+    reader.get_ref().set_read_timeout(Some(TIMEOUT))?;
+    answered
+}
+
+fn receive(reader: &mut impl Read, job: usize, area: usize) -> io::Result<Vec<Pixel>> {
+    if tag(reader)? != RESULT || number(reader)? != job as u64 {
+        return Err(unexpected("expected the result of the job just sent"));
+    }
+
+    let mut samples = vec![Pixel::empty(); area];
+    for pixel in samples.iter_mut() {
+        for value in pixel.values.iter_mut() {
+            let mut channel = || number(reader).map(f64::from_bits);
+            *value = Vec3::new(channel()?, channel()?, channel()?);
+        }
+    }
+    Ok(samples)
+}
+
+// render by handing jobs to workers that connect to the listener,
+// with the same stopping, progress and checkpoints as rendering locally.
+// jobs still out when the render is stopped are dropped, not waited for.
+// counting rays happens in the workers, so only samples and time are in the stats
+pub fn serve(listener: TcpListener, camera: Camera, scene: Scene, control: &Control) -> io::Result<(Image<Pixel>, Stats)> {
+    let jobs = camera.tiles().len() * camera.aa;
+    let state = control.start(&camera, &scene)?;
+
+    let waiting: VecDeque<usize> = (0..jobs).filter(|job| !state.done[*job]).collect();
+    let shared = Shared {
+        tiles: camera.tiles(),
+        camera, control,
+        fingerprint: state.fingerprint,
+        remaining: AtomicUsize::new(waiting.len()),
+        taken: AtomicUsize::new(0),
+        queue: Mutex::new(waiting),
+        state: Mutex::new(state),
+        saved: Mutex::new(Instant::now()),
+        start: Instant::now(),
+    };
+
+    listener.set_nonblocking(true).expect("could not poll for workers");
+    thread::scope(|scope| {
+        while !shared.over() {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    let shared = &shared;
+                    scope.spawn(move || {
+                        if let Err(error) = connection(stream, shared) {
+                            eprintln!("Lost worker {}: {}", address, error);
+                        }
+                    });
+                },
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL),
+                Err(error) => eprintln!("Could not accept worker: {}", error),
+            }
+        }
+    });
+
+    control.report(shared.progress(true));
+    let state = shared.state.into_inner().expect("a connection panicked");
+    if let Some((path, _)) = &control.checkpoint { state.keep(path); }
+
+    let mut stats = Stats { samples: state.film.count.data().iter().sum(), ..Stats::default() };
+    stats.phases.push(("render".to_string(), shared.start.elapsed()));
+    let developing = Instant::now();
+    let image = camera.develop(&state.film);
+    stats.phases.push(("develop".to_string(), developing.elapsed()));
+
+    Ok((image, stats))
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::io::{BufReader, BufWriter};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{serve, work, send, tag, number, HELLO, JOB, DONE, REJECT};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::camera::{Camera, TILE};
+    use crate::structures::scene::Scene;
+    use crate::structures::material::Material;
+    use crate::structures::control::Control;
+    use crate::structures::aov::Aov;
+    use crate::objects::sphere::Sphere;
+    use crate::checkpoint::fingerprint;
+    use crate::render::Integrator;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+            60.0, (TILE + 8, TILE + 4), 2, 1, 2,
+        );
+        camera.integrator = Integrator::Path { min_depth: 1 };
+        camera
+    }
+
+    fn scene() -> Scene {
+        let mut scene = Scene::empty();
+        let grey = Material::dielectric(Vec3::new(0.5, 0.5, 0.5), 0.0, 1.0);
+        scene.add_march(Box::new(Sphere::new(Vec3::new(0.0, 0.0, -3.0), 1.0, grey)));
+        scene
+    }
+
+    #[test]
+    fn test_distributed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // one worker takes a job and vanishes, one is for another scene,
+        // and two do the real work
+        let flaky = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            send(&mut BufWriter::new(stream), HELLO, &[fingerprint(&camera(), &scene())]).unwrap();
+            assert_eq!(tag(&mut reader).unwrap(), JOB);
+            number(&mut reader).unwrap()
+        });
+        let stranger = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            send(&mut BufWriter::new(stream), HELLO, &[fingerprint(&camera(), &Scene::empty())]).unwrap();
+            tag(&mut reader).unwrap()
+        });
+
+        let mut control = Control::new();
+        control.seed = 7;
+        let coordinator = thread::spawn(move || serve(listener, camera(), scene(), &control));
+
+        let abandoned = flaky.join().unwrap();
+        assert_eq!(stranger.join().unwrap(), REJECT);
+        let workers: Vec<_> = (0..2).map(|_| thread::spawn(move || work(address, camera(), scene()).unwrap())).collect();
+
+        let (image, stats) = coordinator.join().unwrap().unwrap();
+        let rendered: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+        assert!(abandoned < 8);
+        assert_eq!(rendered, 8);
+        assert_eq!(stats.samples, (TILE + 8) * (TILE + 4) * 2);
+
+        // the same as rendering it all here
+        let mut control = Control::new();
+        control.seed = 7;
+        let (local, _) = camera().render_with(scene(), &control).unwrap();
+        for (a, b) in image.data().iter().zip(local.data().iter()) {
+            assert_eq!(a.get(Aov::Beauty), b.get(Aov::Beauty));
+        }
+    }
+
+    #[test]
+    fn test_stopping() {
+        // the budget covers one pass of the two
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut control = Control::new();
+        control.samples = Some((TILE + 8) * (TILE + 4));
+        let start = Instant::now();
+        let coordinator = thread::spawn(move || serve(listener, camera(), scene(), &control));
+
+        // a connection that never says hello doesn't hold it up
+        let _silent = TcpStream::connect(address).unwrap();
+        let rendered = work(address, camera(), scene()).unwrap();
+        let (_, stats) = coordinator.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(rendered, 4);
+        assert_eq!(stats.samples, (TILE + 8) * (TILE + 4));
+
+        // a worker sitting on its job doesn't hold up a render that's out of time
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut control = Control::new();
+        control.time = Some(Duration::from_millis(500));
+        let start = Instant::now();
+        let coordinator = thread::spawn(move || serve(listener, camera(), scene(), &control));
+
+        let stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        send(&mut BufWriter::new(stream), HELLO, &[fingerprint(&camera(), &scene())]).unwrap();
+        assert_eq!(tag(&mut reader).unwrap(), JOB);
+        number(&mut reader).unwrap();
+        number(&mut reader).unwrap();
+
+        assert_eq!(tag(&mut reader).unwrap(), DONE);
+        let (_, stats) = coordinator.join().unwrap().unwrap();
+        assert_eq!(stats.samples, 0);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
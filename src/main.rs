@@ -1,15 +1,101 @@
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 // use tokio::prelude::*;
 
 mod structures;
 mod objects;
+mod bsdf;
 mod write;
 mod render;
+mod denoise;
+mod checkpoint;
+mod distribute;
+mod compare;
 mod demo;
+#[cfg(feature = "window")]
+mod window;
 
-// runs the demo
+use structures::aov::Aov;
+use denoise::Denoiser;
+use render::Integrator;
+use structures::scene::Scene;
+use structures::camera::{Camera, Region};
+use structures::film::Tile;
+use structures::control::Control;
+use compare::{Difference, Tolerance};
+
+// how often to save a checkpoint, when asked to
+const CHECKPOINT_EVERY: Duration = Duration::from_secs(5 * 60);
+
+// renders one of the demos, the mandelbulb unless another is picked
 fn main() {
+    // check a render against a reference instead, e.g. for regression tests
+    if env::args().nth(1).as_deref() == Some("compare") {
+        return compare_renders();
+    }
+
+    // which scene to render, e.g. `--demo forest`
+    let name = flag("--demo").unwrap_or_else(|| "mandelbulb".to_string());
+    let (scene, mut camera) = match demo::named(&name) {
+        Some(demo) => demo(),
+        None => {
+            let names: Vec<&str> = demo::DEMOS.iter().map(|(name, _)| *name).collect();
+            eprintln!("No demo called {}, expected one of: {}", name, names.join(", "));
+            return;
+        },
+    };
+
+    // trade detail for a clean image at low sample counts
+    if env::args().any(|arg| arg == "--denoise") {
+        camera.denoiser = Denoiser::atrous();
+    }
+
+    // a quick look, without path tracing.
+    // preview shading has no noise, so one sample per pixel will do
+    if env::args().any(|arg| arg == "--preview") {
+        camera.integrator = Integrator::Preview;
+        camera.aa = 1;
+    }
+
+    // render just part of the frame, e.g. `--crop 100,50,64,64` for 64x64 pixels
+    // from 100 across and 50 down, or `--border` to keep it in a full size image
+    let region = |name| flag(name).and_then(|rect| {
+        let numbers: Vec<usize> = rect.split(',').filter_map(|number| number.parse().ok()).collect();
+        match numbers[..] {
+            [x, y, width, height] => Some(Tile { x, y, width, height }),
+            _ => { eprintln!("Expected x,y,width,height after {}", name); None },
+        }
+    });
+    if let Some(crop) = region("--crop") {
+        camera.region = Region::Crop(crop);
+    }
+    if let Some(border) = region("--border") {
+        camera.region = Region::Border(border);
+    }
+
+    // fly around the scene instead of saving a render.
+    // the window always shows the whole frame
+    if env::args().any(|arg| arg == "--window") {
+        if camera.region != Region::Full {
+            eprintln!("--crop and --border can't be used with --window");
+            return;
+        }
+        return explore(scene, camera);
+    }
+
+    // help another keikan render, then exit
+    if let Some(address) = flag("--worker") {
+        match distribute::work(&address, camera, scene) {
+            Ok(jobs) => println!("Rendered {} job(s) for {}", jobs, address),
+            Err(error) => eprintln!("Could not work for {}: {}", address, error),
+        }
+        return;
+    }
+
     let output: String = match env::args().nth(1) {
         Some(p) => p,
         None    => {
@@ -18,13 +104,137 @@ fn main() {
         },
     };
 
-    let (scene, camera) = demo::mandelbulb();
-    let image = camera.render(scene);
+    // keep whatever has been rendered after this many seconds
+    let mut control = Control::printing();
+    if let Some(seconds) = flag("--time") {
+        match seconds.parse() {
+            Ok(seconds) => control.time = Some(Duration::from_secs_f64(seconds)),
+            Err(_) => eprintln!("Expected a number of seconds after --time"),
+        }
+    }
 
-    match write::png(image, Path::new(&output.to_string())) {
+    // save progress as the render goes, so it can be picked back up if stopped
+    if let Some(path) = flag("--resume") {
+        if let Err(error) = control.resume(Path::new(&path), &camera, &scene) {
+            eprintln!("Could not resume from {}: {}", path, error);
+            return;
+        }
+        control.checkpoint = Some((PathBuf::from(path), CHECKPOINT_EVERY));
+    }
+    if let Some(path) = flag("--checkpoint") {
+        control.checkpoint = Some((PathBuf::from(path), CHECKPOINT_EVERY));
+    }
+
+    camera.describe(&scene);
+    let rendered = match flag("--serve") {
+        // hand the render out to workers instead
+        Some(address) => match TcpListener::bind(&address) {
+            Ok(listener) => {
+                println!("waiting for workers on {}\n", address);
+                distribute::serve(listener, camera, scene, &control)
+            },
+            Err(error) => {
+                eprintln!("Could not listen on {}: {}", address, error);
+                return;
+            },
+        },
+        None => camera.render_with(scene, &control),
+    };
+    let (image, mut stats) = match rendered {
+        Ok(rendered) => rendered,
+        Err(error) => {
+            eprintln!("Could not render: {}", error);
+            return;
+        },
+    };
+    let path = Path::new(&output);
+
+    // .hdr keeps the linear values, for comparing renders later
+    let saving = Instant::now();
+    let beauty = image.layer(Aov::Beauty);
+    let saved = match path.extension().and_then(|s| s.to_str()) {
+        Some("hdr") => write::hdr(&beauty, path),
+        _ => write::png(&beauty, path),
+    };
+    match saved {
         Ok(())   => (),
         Err(_) => eprintln!("Could not save image!"),
     }
+    stats.phases.push(("save".to_string(), saving.elapsed()));
+
+    // also save depth, normals, ids, etc.
+    if env::args().any(|arg| arg == "--aovs") {
+        match write::aovs(&image, path) {
+            Ok(())   => (),
+            Err(_) => eprintln!("Could not save aovs!"),
+        }
+    }
+
+    // what the render did, for people or for other tools
+    if env::args().any(|arg| arg == "--stats") {
+        stats.print();
+    }
+    if let Some(json) = flag("--stats-json") {
+        match fs::write(&json, stats.json()) {
+            Ok(())   => println!("Statistics saved to {}", json),
+            Err(_) => eprintln!("Could not save statistics!"),
+        }
+    }
+}
+
+// the argument after a flag, e.g. `--time 60`
+fn flag(name: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+// `keikan compare render.hdr reference.hdr`, printing how far apart they are.
+// exits with 1 if they're further apart than the tolerance, or 2 if they can't be compared
+fn compare_renders() {
+    let fail = |message: String| -> ! {
+        eprintln!("{}", message);
+        process::exit(2);
+    };
+
+    let paths: Vec<String> = env::args().skip(2).take(2).collect();
+    if paths.len() < 2 || paths.iter().any(|path| path.starts_with("--")) {
+        fail("Expected two images: keikan compare render.png reference.png".to_string());
+    }
+    let load = |path: &String| compare::load(Path::new(path))
+        .unwrap_or_else(|error| fail(format!("Could not read {}: {}", path, error)));
+    let (image, reference) = (load(&paths[0]), load(&paths[1]));
+
+    let difference = Difference::between(&image, &reference).unwrap_or_else(|error| fail(error.to_string()));
+    difference.print();
+
+    // red and white where they differ most
+    if let Some(path) = flag("--heatmap") {
+        if let Err(error) = write::colors(&compare::heatmap(&image, &reference), Path::new(&path)) {
+            eprintln!("Could not save heatmap: {}", error);
+        }
+    }
+
+    // the most relative mse, and the least ssim, that still passes
+    let mut tolerance = Tolerance::new();
+    let number = |name| flag(name).map(|value| value.parse()
+        .unwrap_or_else(|_| fail(format!("Expected a number after {}", name))));
+    if let Some(rel_mse) = number("--threshold") { tolerance.rel_mse = rel_mse; }
+    if let Some(ssim) = number("--min-ssim") { tolerance.ssim = ssim; }
+
+    let failures = difference.failures(&tolerance);
+    if failures.is_empty() {
+        println!("\nPassed");
+    } else {
+        println!("\nFailed: {}", failures.join(", "));
+        process::exit(1);
+    }
 }
 
-// TODO: write tests
+#[cfg(feature = "window")]
+fn explore(scene: Scene, camera: Camera) {
+    window::open(scene, camera);
+}
+
+#[cfg(not(feature = "window"))]
+fn explore(_scene: Scene, _camera: Camera) {
+    eprintln!("Keikan was built without the preview window, rebuild with `--features window`");
+}
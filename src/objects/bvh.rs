@@ -0,0 +1,233 @@
+use std::cmp::Ordering;
+
+use crate::structures::vec3::Vec3;
+use crate::structures::ray::Ray;
+use crate::structures::material::Material;
+use crate::structures::cast::Cast;
+use crate::structures::aabb::Aabb;
+use crate::objects::trace::Trace;
+use crate::render::EPSILON;
+
+// most objects tested at once, past this nodes are split
+const LEAF_SIZE: usize = 4;
+// deepest the tree can be, more than enough for median splits
+const MAX_DEPTH: usize = 64;
+
+enum Node {
+    Leaf { bounds: Aabb, start: usize, end: usize },
+    Branch { bounds: Aabb, left: usize, right: usize },
+}
+
+impl Node {
+    fn bounds(&self) -> Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Branch { bounds, .. } => *bounds,
+        }
+    }
+}
+
+// a group of objects in a bounding volume hierarchy,
+// so a ray only has to test the few objects it passes near.
+// a bvh of triangles is a mesh.
+pub struct Bvh {
+    objects: Vec<Box<dyn Trace>>, // ordered so that each leaf is a range
+    unbounded: Vec<Box<dyn Trace>>, // always tested
+    nodes: Vec<Node>, // the root is first
+}
+
+// split along the axis the objects are most spread out on, at the median
+fn build(items: &mut [(Aabb, Box<dyn Trace>)], start: usize, nodes: &mut Vec<Node>) -> usize {
+    let bounds = items.iter().fold(Aabb::empty(), |bounds, (b, _)| bounds.union(b));
+    let index = nodes.len();
+    nodes.push(Node::Leaf { bounds, start, end: start + items.len() });
+    if items.len() <= LEAF_SIZE { return index; }
+
+    let centers = items.iter().fold(Aabb::empty(), |centers, (b, _)| centers.grow(b.center()));
+    let extent = centers.max - centers.min;
+    let axis = |v: Vec3| {
+        if extent.x >= extent.y && extent.x >= extent.z { v.x }
+        else if extent.y >= extent.z { v.y }
+        else { v.z }
+    };
+
+    items.sort_by(|(a, _), (b, _)| {
+        axis(a.center()).partial_cmp(&axis(b.center())).unwrap_or(Ordering::Equal)
+    });
+
+    let middle = items.len() / 2;
+    let (low, high) = items.split_at_mut(middle);
+    let left = build(low, start, nodes);
+    let right = build(high, start + middle, nodes);
+
+    nodes[index] = Node::Branch { bounds, left, right };
+    index
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn Trace>>) -> Bvh {
+        let mut items = vec![];
+        let mut unbounded = vec![];
+
+        for object in objects {
+            match object.bounds() {
+                Some(bounds) => items.push((bounds, object)),
+                None => unbounded.push(object),
+            }
+        }
+
+        let mut nodes = vec![];
+        if !items.is_empty() { build(&mut items, 0, &mut nodes); }
+        let objects = items.into_iter().map(|(_, object)| object).collect();
+
+        Bvh { objects, unbounded, nodes }
+    }
+}
+
+impl Trace for Bvh {
+    // the group has no single material, so report the first
+    fn material(&self) -> Material {
+        match self.objects.first().or_else(|| self.unbounded.first()) {
+            Some(object) => object.material(),
+            None => Material::sky(),
+        }
+    }
+
+    fn trace(&self, ray: Ray) -> Option<(f64, Vec3)> {
+        let cast = self.cast(ray)?;
+        Some((cast.distance, cast.normal))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() { return None; }
+        Some(self.nodes.first().map_or(Aabb::empty(), |root| root.bounds()))
+    }
+
+    fn cast(&self, ray: Ray) -> Option<Cast> {
+        let mut best: Option<Cast> = None;
+        let nearest = |best: &Option<Cast>| best.map_or(f64::MAX, |cast| cast.distance);
+
+        let consider = |best: &mut Option<Cast>, object: &dyn Trace| {
+            if let Some(cast) = object.cast(ray) {
+                if cast.distance > EPSILON && cast.distance < nearest(best) {
+                    *best = Some(cast);
+                }
+            }
+        };
+
+        for object in self.unbounded.iter() {
+            consider(&mut best, object.as_ref());
+        }
+
+        let root = match self.nodes.first() {
+            Some(root) => root,
+            None => return best,
+        };
+        if root.bounds().hit(ray, nearest(&best)).is_none() { return best; }
+
+        let mut stack = [0; MAX_DEPTH];
+        let mut size = 1;
+
+        while size > 0 {
+            size -= 1;
+
+            match self.nodes[stack[size]] {
+                Node::Leaf { start, end, .. } => {
+                    for object in self.objects[start..end].iter() {
+                        consider(&mut best, object.as_ref());
+                    }
+                },
+                Node::Branch { left, right, .. } => {
+                    let max = nearest(&best);
+                    let near = self.nodes[left].bounds().hit(ray, max);
+                    let far = self.nodes[right].bounds().hit(ray, max);
+
+                    // visit the closer child first, so it's on top of the stack
+                    let order = match (near, far) {
+                        (Some(a), Some(b)) if b < a => [Some(left), Some(right)],
+                        _ => [far.map(|_| right), near.map(|_| left)],
+                    };
+
+                    for child in order.iter().flatten() {
+                        stack[size] = *child;
+                        size += 1;
+                    }
+                },
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::Bvh;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::material::Material;
+    use crate::objects::trace::Trace;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::triangle::Triangle;
+    use crate::objects::plane::Plane;
+
+    fn point(rng: &mut StdRng) -> Vec3 {
+        Vec3::new(rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5) * 10.0
+    }
+
+    // a jumble of shapes, each with its own material
+    fn shapes(rng: &mut StdRng) -> Vec<Box<dyn Trace>> {
+        let mut shapes: Vec<Box<dyn Trace>> = vec![];
+
+        for i in 0..100 {
+            let material = Material::emissive(Vec3::new(i as f64, 0.0, 0.0), 1.0);
+            let center = point(rng);
+
+            if i % 2 == 0 {
+                shapes.push(Box::new(Sphere::new(center, rng.gen::<f64>() * 0.5, material)));
+            } else {
+                let (b, c) = (center + point(rng) * 0.1, center + point(rng) * 0.1);
+                shapes.push(Box::new(Triangle::new(center, b, c, material)));
+            }
+        }
+
+        let floor = Material::emissive(Vec3::new(-1.0, 0.0, 0.0), 1.0);
+        shapes.push(Box::new(Plane::new(Vec3::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0), floor)));
+        shapes
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(35);
+        let bvh = Bvh::new(shapes(&mut rng));
+        let mut rng = StdRng::seed_from_u64(35);
+        let all = shapes(&mut rng);
+
+        for _ in 0..2000 {
+            let ray = Ray::new(point(&mut rng), point(&mut rng).unit());
+            let expected = Trace::hit(&all, ray);
+            let cast = bvh.cast(ray);
+
+            match (expected, cast) {
+                (None, None) => (),
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.distance, b.distance);
+                    assert_eq!(a.material.color, b.material.color);
+                },
+                _ => panic!("bvh and brute force disagree"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut rng = StdRng::seed_from_u64(36);
+        let mut shapes = shapes(&mut rng);
+        assert!(Bvh::new(shapes.split_off(100)).bounds().is_none());
+
+        let bounds = Bvh::new(shapes).bounds().unwrap();
+        assert!(bounds.min.x >= -6.0 && bounds.max.x <= 6.0);
+    }
+}
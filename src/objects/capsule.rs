@@ -0,0 +1,51 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// line segment from a to b, thickened by radius
+#[derive(Debug, Copy, Clone)]
+pub struct Capsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: f64, material: Material) -> Capsule {
+        Capsule { a, b, radius, material }
+    }
+}
+
+impl March for Capsule {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        let pa = point - self.a;
+        let ba = self.b - self.a;
+        // closest point along the segment
+        let h = (pa.dot(&ba) / ba.dot(&ba)).clamp(0.0, 1.0);
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::empty().grow(self.a).grow(self.b).pad(self.radius))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Capsule;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let capsule = Capsule::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), 0.5, Material::sky());
+        assert_eq!(capsule.march(Vec3::new(1.0, 1.0, 0.0)), 0.5);
+        assert_eq!(capsule.march(Vec3::new(0.0, 4.0, 0.0)), 1.5);
+        assert_eq!(capsule.march(Vec3::new(0.0, 1.0, 0.0)), -0.5);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// how the seam between two combined objects is shaped.
+// smooth blends take a radius, the distance over which the objects merge.
+#[derive(Debug, Copy, Clone)]
+pub enum Blend {
+    Hard,
+    Polynomial(f64), // quadratic smooth min, only affects points within the radius
+    Exponential(f64), // exponential smooth min, smoother but affects everything a little
+}
+
+impl Blend {
+    // a smooth blend without a positive radius is just a hard one
+    fn settle(self) -> Blend {
+        match self {
+            Blend::Polynomial(k) | Blend::Exponential(k) if k <= 0.0 => Blend::Hard,
+            other => other,
+        }
+    }
+
+    // the (smooth) minimum of a and b,
+    // along with how much of b went into it, from 0 to 1
+    pub fn min(self, a: f64, b: f64) -> (f64, f64) {
+        match self.settle() {
+            Blend::Hard => if b < a { (b, 1.0) } else { (a, 0.0) },
+            // from iquilezles.org/www/articles/smin/smin.htm
+            Blend::Polynomial(k) => {
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                (b * (1.0 - h) + a * h - k * h * (1.0 - h), 1.0 - h)
+            },
+            Blend::Exponential(k) => {
+                // shift by the minimum to keep the exponentials in range
+                let low = a.min(b);
+                let (ea, eb) = ((-(a - low) / k).exp2(), (-(b - low) / k).exp2());
+                (low - (ea + eb).log2() * k, eb / (ea + eb))
+            },
+        }
+    }
+
+    pub fn max(self, a: f64, b: f64) -> (f64, f64) {
+        let (distance, t) = self.min(-a, -b);
+        (-distance, t)
+    }
+
+    // smooth unions swell past both objects, by at most this much
+    fn pad(self, bounds: Aabb) -> Aabb {
+        match self.settle() {
+            Blend::Hard => bounds,
+            Blend::Polynomial(k) => bounds.pad(k / 4.0),
+            Blend::Exponential(k) => bounds.pad(k),
+        }
+    }
+}
+
+// material of the combination, blended across the seam
+fn blend(a: &dyn March, b: &dyn March, point: Vec3, t: f64) -> Material {
+    if t <= 0.0 { return a.material_at(point); }
+    if t >= 1.0 { return b.material_at(point); }
+    Material::lerp(a.material_at(point), b.material_at(point), t)
+}
+
+// everything inside either object
+pub struct Union {
+    pub a: Box<dyn March>,
+    pub b: Box<dyn March>,
+    pub blend: Blend,
+}
+
+impl Union {
+    pub fn new(a: Box<dyn March>, b: Box<dyn March>, blend: Blend) -> Union {
+        Union { a, b, blend }
+    }
+
+    // children are combined by their bounds, which already undo any stretching,
+    // so combinations keep the default lipschitz constant of 1
+    fn combine(&self, point: Vec3) -> (f64, f64) {
+        self.blend.min(self.a.bound(point), self.b.bound(point))
+    }
+}
+
+impl March for Union {
+    fn material(&self) -> Material { self.a.material() }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.combine(point).0
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        blend(&*self.a, &*self.b, point, self.combine(point).1)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.blend.pad(self.a.bounds()?.union(&self.b.bounds()?)))
+    }
+}
+
+// everything inside both objects
+pub struct Intersection {
+    pub a: Box<dyn March>,
+    pub b: Box<dyn March>,
+    pub blend: Blend,
+}
+
+impl Intersection {
+    pub fn new(a: Box<dyn March>, b: Box<dyn March>, blend: Blend) -> Intersection {
+        Intersection { a, b, blend }
+    }
+
+    fn combine(&self, point: Vec3) -> (f64, f64) {
+        self.blend.max(self.a.bound(point), self.b.bound(point))
+    }
+}
+
+impl March for Intersection {
+    fn material(&self) -> Material { self.a.material() }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.combine(point).0
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        blend(&*self.a, &*self.b, point, self.combine(point).1)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        match (self.a.bounds(), self.b.bounds()) {
+            (Some(a), Some(b)) => Some(a.intersect(&b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+// a with b carved out of it,
+// the carved surface takes on the material of b
+pub struct Difference {
+    pub a: Box<dyn March>,
+    pub b: Box<dyn March>,
+    pub blend: Blend,
+}
+
+impl Difference {
+    pub fn new(a: Box<dyn March>, b: Box<dyn March>, blend: Blend) -> Difference {
+        Difference { a, b, blend }
+    }
+
+    fn combine(&self, point: Vec3) -> (f64, f64) {
+        self.blend.max(self.a.bound(point), -self.b.bound(point))
+    }
+}
+
+impl March for Difference {
+    fn material(&self) -> Material { self.a.material() }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.combine(point).0
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        blend(&*self.a, &*self.b, point, self.combine(point).1)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{Blend, Union, Intersection, Difference};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+    use crate::objects::sphere::Sphere;
+
+    fn red() -> Material { Material::dielectric(Vec3::new(1.0, 0.0, 0.0), 0.5, 0.5) }
+    fn blue() -> Material { Material::dielectric(Vec3::new(0.0, 0.0, 1.0), 0.5, 0.5) }
+
+    // two unit spheres, two units apart along x
+    fn pair() -> (Box<Sphere>, Box<Sphere>) {
+        (
+            Box::new(Sphere::new(Vec3::new(-1.0, 0.0, 0.0), 1.0, red())),
+            Box::new(Sphere::new(Vec3::new( 1.0, 0.0, 0.0), 1.0, blue())),
+        )
+    }
+
+    #[test]
+    fn test_hard() {
+        let (a, b) = pair();
+        let union = Union::new(a, b, Blend::Hard);
+        assert_eq!(union.march(Vec3::new(3.0, 0.0, 0.0)), 1.0);
+        assert_eq!(union.material_at(Vec3::new(2.0, 0.0, 0.0)).color, blue().color);
+
+        let (a, b) = pair();
+        let intersection = Intersection::new(a, b, Blend::Hard);
+        assert_eq!(intersection.march(Vec3::new(0.0, 0.0, 0.0)), 0.0);
+        assert_eq!(intersection.march(Vec3::new(-2.0, 0.0, 0.0)), 2.0);
+
+        let (a, b) = pair();
+        let difference = Difference::new(a, b, Blend::Hard);
+        assert_eq!(difference.march(Vec3::new(-1.0, 0.0, 0.0)), -1.0);
+        assert_eq!(difference.march(Vec3::new(0.5, 0.0, 0.0)), 0.5);
+    }
+
+    #[test]
+    fn test_smooth() {
+        for &blend in [Blend::Polynomial(0.5), Blend::Exponential(0.1)].iter() {
+            let (a, b) = pair();
+            let union = Union::new(a, b, blend);
+
+            // the seam fills in, and takes half of each material
+            let seam = Vec3::new(0.0, 1.0, 0.0);
+            let hard = (seam - Vec3::new(1.0, 0.0, 0.0)).length() - 1.0;
+            assert!(union.march(seam) < hard);
+
+            let color = union.material_at(seam).color;
+            assert!((color.x - 0.5).abs() < 1e-9 && (color.z - 0.5).abs() < 1e-9);
+        }
+
+        // the polynomial blend leaves far away points alone
+        let (a, b) = pair();
+        let union = Union::new(a, b, Blend::Polynomial(0.5));
+        assert_eq!(union.march(Vec3::new(5.0, 0.0, 0.0)), 3.0);
+    }
+
+    #[test]
+    fn test_zero_radius() {
+        // no radius, or a negative one, is a hard blend rather than nan
+        for &blend in [Blend::Polynomial(0.0), Blend::Exponential(0.0), Blend::Polynomial(-1.0), Blend::Exponential(-1.0)].iter() {
+            assert_eq!(blend.min(1.0, 1.0), Blend::Hard.min(1.0, 1.0));
+            assert_eq!(blend.min(2.0, 0.5), (0.5, 1.0));
+            assert_eq!(blend.max(2.0, 0.5), (2.0, 0.0));
+
+            let (a, b) = pair();
+            let union = Union::new(a, b, blend);
+            assert_eq!(union.march(Vec3::new(3.0, 0.0, 0.0)), 1.0);
+            assert_eq!(union.march(Vec3::new(0.0, 1.0, 0.0)), 2.0_f64.sqrt() - 1.0);
+        }
+    }
+}
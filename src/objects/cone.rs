@@ -0,0 +1,70 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// upright cone, cut flat at the top and bottom.
+// a top radius of 0 makes a pointed cone.
+#[derive(Debug, Copy, Clone)]
+pub struct Cone {
+    pub position: Vec3, // center
+    pub height: f64, // half the distance between the caps
+    pub bottom: f64, // radius of the bottom cap
+    pub top: f64, // radius of the top cap
+    pub material: Material,
+}
+
+impl Cone {
+    pub fn new(position: Vec3, height: f64, bottom: f64, top: f64, material: Material) -> Cone {
+        Cone { position, height, bottom, top, material }
+    }
+}
+
+impl March for Cone {
+    fn material(&self) -> Material { self.material }
+
+    // exact capped cone from iquilezles.org/www/articles/distfunctions/distfunctions.htm,
+    // worked out in the 2d plane through the axis
+    fn march(&self, point: Vec3) -> f64 {
+        let p = point - self.position;
+        let (qx, qy) = ((p.x * p.x + p.z * p.z).sqrt(), p.y);
+        let (h, r1, r2) = (self.height, self.bottom, self.top);
+
+        // closest point on the caps
+        let cap = if qy < 0.0 { r1 } else { r2 };
+        let (ax, ay) = (qx - qx.min(cap), qy.abs() - h);
+
+        // closest point on the slanted side
+        let (k2x, k2y) = (r2 - r1, 2.0 * h);
+        let t = (((r2 - qx) * k2x + (h - qy) * k2y) / (k2x * k2x + k2y * k2y)).clamp(0.0, 1.0);
+        let (bx, by) = (qx - r2 + k2x * t, qy - h + k2y * t);
+
+        let sign = if bx < 0.0 && ay < 0.0 { -1.0 } else { 1.0 };
+        sign * (ax * ax + ay * ay).min(bx * bx + by * by).sqrt()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let radius = self.bottom.max(self.top);
+        Some(Aabb::around(self.position, Vec3::new(radius, self.height, radius)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Cone;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let cone = Cone::new(Vec3::new(0.0, 0.0, 0.0), 1.0, 1.0, 0.0, Material::sky());
+        assert_eq!(cone.march(Vec3::new(0.0, 3.0, 0.0)), 2.0);
+        assert_eq!(cone.march(Vec3::new(0.0, -2.0, 0.0)), 1.0);
+        assert!(cone.march(Vec3::new(0.0, 0.0, 0.0)) < 0.0);
+
+        // straight sides make a cylinder
+        let cylinder = Cone::new(Vec3::new(0.0, 0.0, 0.0), 1.0, 0.5, 0.5, Material::sky());
+        assert_eq!(cylinder.march(Vec3::new(2.0, 0.0, 0.0)), 1.5);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// axis-aligned box, optionally with rounded edges
+#[derive(Debug, Copy, Clone)]
+pub struct Cuboid {
+    pub position: Vec3,
+    pub size: Vec3, // half the length of each side
+    pub radius: f64, // rounding of the edges, within size
+    pub material: Material,
+}
+
+impl Cuboid {
+    pub fn new(position: Vec3, size: Vec3, material: Material) -> Cuboid {
+        Cuboid { position, size, radius: 0.0, material }
+    }
+
+    pub fn rounded(position: Vec3, size: Vec3, radius: f64, material: Material) -> Cuboid {
+        Cuboid { position, size, radius, material }
+    }
+}
+
+impl March for Cuboid {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        let q = (point - self.position).abs() - self.size + self.radius;
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        let inside = q.max_element().min(0.0);
+        outside + inside - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, self.size))
+    }
+
+    // out from the nearest point on the box, or the nearest face from inside
+    fn gradient(&self, point: Vec3) -> Option<Vec3> {
+        let p = point - self.position;
+        let q = p.abs() - self.size + self.radius;
+
+        if q.max_element() > 0.0 {
+            return Some(Vec3::new(
+                q.x.max(0.0) * p.x.signum(),
+                q.y.max(0.0) * p.y.signum(),
+                q.z.max(0.0) * p.z.signum(),
+            ));
+        }
+
+        let largest = q.max_element();
+        let face = |x: f64, q: f64| if q == largest { x.signum() } else { 0.0 };
+        Some(Vec3::new(face(p.x, q.x), face(p.y, q.y), face(p.z, q.z)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Cuboid;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let cuboid = Cuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0), Material::sky());
+        assert_eq!(cuboid.march(Vec3::new(3.0, 0.0, 0.0)), 2.0);
+        assert_eq!(cuboid.march(Vec3::new(0.0, 0.0, 0.0)), -1.0);
+        assert_eq!(cuboid.march(Vec3::new(4.0, 6.0, 0.0)), 5.0);
+
+        let rounded = Cuboid::rounded(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), 0.5, Material::sky());
+        assert_eq!(rounded.march(Vec3::new(2.0, 0.0, 0.0)), 1.0);
+        let corner = rounded.march(Vec3::new(1.0, 1.0, 1.0));
+        assert!((corner - (0.75_f64.sqrt() - 0.5)).abs() < 1e-9);
+    }
+}
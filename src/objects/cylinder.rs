@@ -0,0 +1,53 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// upright cylinder with flat caps
+#[derive(Debug, Copy, Clone)]
+pub struct Cylinder {
+    pub position: Vec3, // center
+    pub height: f64, // half the distance between the caps
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Cylinder {
+    pub fn new(position: Vec3, height: f64, radius: f64, material: Material) -> Cylinder {
+        Cylinder { position, height, radius, material }
+    }
+}
+
+impl March for Cylinder {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        let p = point - self.position;
+        let dx = (p.x * p.x + p.z * p.z).sqrt() - self.radius;
+        let dy = p.y.abs() - self.height;
+
+        let outside = (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt();
+        outside + dx.max(dy).min(0.0)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, Vec3::new(self.radius, self.height, self.radius)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Cylinder;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let cylinder = Cylinder::new(Vec3::new(0.0, 0.0, 0.0), 1.0, 0.5, Material::sky());
+        assert_eq!(cylinder.march(Vec3::new(2.0, 0.0, 0.0)), 1.5);
+        assert_eq!(cylinder.march(Vec3::new(0.0, 3.0, 0.0)), 2.0);
+        assert_eq!(cylinder.march(Vec3::new(0.0, 0.0, 0.0)), -0.5);
+        assert_eq!(cylinder.march(Vec3::new(3.5, 5.0, 0.0)), 5.0);
+    }
+}
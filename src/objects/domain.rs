@@ -0,0 +1,285 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// operators that move the point an object is sampled at,
+// which repeats, folds and distorts the space it lives in.
+
+// repeat a coordinate every period, up to count times on either side of 0.
+// a period of 0 leaves the axis alone.
+fn repeat(x: f64, period: f64, count: f64) -> f64 {
+    if period == 0.0 { return x; }
+    x - period * (x / period).round().clamp(-count, count)
+}
+
+// copies of an object on a grid of cells centered on the origin.
+// the object should sit in the middle cell and fit inside it,
+// otherwise the distance to copies in neighboring cells is missed.
+pub struct Repeat {
+    pub inner: Box<dyn March>,
+    pub period: Vec3, // size of each cell
+    pub count: Vec3, // copies on either side of the middle cell
+}
+
+impl Repeat {
+    pub fn infinite(inner: Box<dyn March>, period: Vec3) -> Repeat {
+        let count = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        Repeat { inner, period, count }
+    }
+
+    pub fn bounded(inner: Box<dyn March>, period: Vec3, count: Vec3) -> Repeat {
+        Repeat { inner, period, count }
+    }
+
+    fn warp(&self, point: Vec3) -> Vec3 {
+        Vec3::new(
+            repeat(point.x, self.period.x, self.count.x),
+            repeat(point.y, self.period.y, self.count.y),
+            repeat(point.z, self.period.z, self.count.z),
+        )
+    }
+}
+
+impl March for Repeat {
+    fn material(&self) -> Material { self.inner.material() }
+    fn march(&self, point: Vec3) -> f64 { self.inner.march(self.warp(point)) }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(self.warp(point)) }
+    fn lipschitz(&self) -> f64 { self.inner.lipschitz() }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // a copy sits in every cell out to count, so infinite repeats are unbounded
+        let reach = self.period.abs() * self.count;
+        let reach = Vec3::new(
+            if self.period.x == 0.0 { 0.0 } else { reach.x },
+            if self.period.y == 0.0 { 0.0 } else { reach.y },
+            if self.period.z == 0.0 { 0.0 } else { reach.z },
+        );
+        if !reach.max_element().is_finite() { return None; }
+        let bounds = self.inner.bounds()?;
+        Some(Aabb::new(bounds.min - reach, bounds.max + reach))
+    }
+}
+
+// reflects whatever is in front of a plane to behind it
+pub struct Mirror {
+    pub inner: Box<dyn March>,
+    pub position: Vec3, // any point on the plane
+    pub normal: Vec3,
+}
+
+impl Mirror {
+    pub fn new(inner: Box<dyn March>, position: Vec3, normal: Vec3) -> Mirror {
+        Mirror { inner, position, normal: normal.unit() }
+    }
+
+    fn warp(&self, point: Vec3) -> Vec3 {
+        let behind = (point - self.position).dot(&self.normal).min(0.0);
+        point - self.normal * (2.0 * behind)
+    }
+}
+
+impl March for Mirror {
+    fn material(&self) -> Material { self.inner.material() }
+    fn march(&self, point: Vec3) -> f64 { self.inner.march(self.warp(point)) }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(self.warp(point)) }
+    fn lipschitz(&self) -> f64 { self.inner.lipschitz() }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.inner.bounds()?;
+        let reflect = |corner: Vec3| corner - self.normal * (2.0 * (corner - self.position).dot(&self.normal));
+        Some(bounds.corners().iter().fold(bounds, |bounds, corner| bounds.grow(reflect(*corner))))
+    }
+}
+
+// spins an object around the vertical axis through position,
+// by rate radians per unit of height.
+// radius is how far from the axis the bound has to hold,
+// at least as far as the object reaches.
+// the further out, the more the twist stretches space.
+pub struct Twist {
+    pub inner: Box<dyn March>,
+    pub position: Vec3,
+    pub rate: f64,
+    pub radius: f64,
+}
+
+impl Twist {
+    pub fn new(inner: Box<dyn March>, position: Vec3, rate: f64, radius: f64) -> Twist {
+        Twist { inner, position, rate, radius }
+    }
+
+    fn warp(&self, point: Vec3) -> Vec3 {
+        let p = point - self.position;
+        let (sin, cos) = (self.rate * p.y).sin_cos();
+        self.position + Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z)
+    }
+}
+
+impl March for Twist {
+    fn material(&self) -> Material { self.inner.material() }
+    fn march(&self, point: Vec3) -> f64 { self.inner.march(self.warp(point)) }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(self.warp(point)) }
+
+    // the twist is a rotation plus a shear of rate * radius,
+    // which stretches by at most 1 + rate * radius
+    fn lipschitz(&self) -> f64 {
+        self.inner.lipschitz() * (1.0 + self.rate.abs() * self.radius)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // everything the object sweeps out as it spins around the axis
+        let bounds = self.inner.bounds()?;
+        let reach = bounds.corners().iter()
+            .map(|corner| { let p = *corner - self.position; (p.x * p.x + p.z * p.z).sqrt() })
+            .fold(0.0, f64::max);
+        let (low, high) = (bounds.min.y, bounds.max.y);
+        Some(Aabb::new(
+            Vec3::new(self.position.x - reach, low, self.position.z - reach),
+            Vec3::new(self.position.x + reach, high, self.position.z + reach),
+        ))
+    }
+}
+
+// curls the x axis around the z axis through position,
+// by rate radians per unit along x.
+// radius is how far from position the bound has to hold.
+pub struct Bend {
+    pub inner: Box<dyn March>,
+    pub position: Vec3,
+    pub rate: f64,
+    pub radius: f64,
+}
+
+impl Bend {
+    pub fn new(inner: Box<dyn March>, position: Vec3, rate: f64, radius: f64) -> Bend {
+        Bend { inner, position, rate, radius }
+    }
+
+    fn warp(&self, point: Vec3) -> Vec3 {
+        let p = point - self.position;
+        let (sin, cos) = (self.rate * p.x).sin_cos();
+        self.position + Vec3::new(cos * p.x - sin * p.y, sin * p.x + cos * p.y, p.z)
+    }
+}
+
+impl March for Bend {
+    fn material(&self) -> Material { self.inner.material() }
+    fn march(&self, point: Vec3) -> f64 { self.inner.march(self.warp(point)) }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(self.warp(point)) }
+
+    // same shear as the twist, around a different axis
+    fn lipschitz(&self) -> f64 {
+        self.inner.lipschitz() * (1.0 + self.rate.abs() * self.radius)
+    }
+}
+
+// pulls an object apart at position,
+// filling the gap by extruding its middle slice size units each way
+pub struct Elongate {
+    pub inner: Box<dyn March>,
+    pub position: Vec3,
+    pub size: Vec3,
+}
+
+impl Elongate {
+    pub fn new(inner: Box<dyn March>, position: Vec3, size: Vec3) -> Elongate {
+        Elongate { inner, position, size }
+    }
+
+    fn warp(&self, point: Vec3) -> Vec3 {
+        let p = point - self.position;
+        let squash = |x: f64, size: f64| x - x.clamp(-size, size);
+        self.position + Vec3::new(squash(p.x, self.size.x), squash(p.y, self.size.y), squash(p.z, self.size.z))
+    }
+}
+
+impl March for Elongate {
+    fn material(&self) -> Material { self.inner.material() }
+    fn march(&self, point: Vec3) -> f64 { self.inner.march(self.warp(point)) }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(self.warp(point)) }
+    fn lipschitz(&self) -> f64 { self.inner.lipschitz() }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.inner.bounds()?;
+        Some(Aabb::new(bounds.min - self.size.abs(), bounds.max + self.size.abs()))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::{Repeat, Mirror, Twist, Bend, Elongate};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::cuboid::Cuboid;
+
+    fn ball(position: Vec3, radius: f64) -> Box<Sphere> {
+        Box::new(Sphere::new(position, radius, Material::sky()))
+    }
+
+    fn bar() -> Box<Cuboid> {
+        Box::new(Cuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.5, 1.0, 0.3), Material::sky()))
+    }
+
+    // within 1.6 of the origin
+    fn point(rng: &mut StdRng) -> Vec3 {
+        Vec3::new(rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5) * 1.8
+    }
+
+    // the bound never changes faster than the distance it bounds
+    fn assert_bounded(object: &dyn March, rng: &mut StdRng) {
+        for _ in 0..2000 {
+            let (a, b) = (point(rng), point(rng));
+            let change = (object.bound(a) - object.bound(b)).abs();
+            assert!(change <= (a - b).length() + 1e-9, "{} over {}", change, (a - b).length());
+        }
+    }
+
+    #[test]
+    fn test_repeat() {
+        let period = Vec3::new(2.0, 0.0, 0.0);
+        let infinite = Repeat::infinite(ball(Vec3::new(0.0, 0.0, 0.0), 0.5), period);
+        assert_eq!(infinite.march(Vec3::new(40.0, 0.0, 0.0)), -0.5);
+        assert_eq!(infinite.march(Vec3::new(41.0, 1.0, 0.0)), 2.0_f64.sqrt() - 0.5);
+
+        let bounded = Repeat::bounded(ball(Vec3::new(0.0, 0.0, 0.0), 0.5), period, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(bounded.march(Vec3::new(-2.0, 0.0, 0.0)), -0.5);
+        assert_eq!(bounded.march(Vec3::new(4.0, 0.0, 0.0)), 1.5);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mirror = Mirror::new(ball(Vec3::new(2.0, 0.0, 0.0), 1.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(mirror.march(Vec3::new(-2.0, 0.0, 0.0)), -1.0);
+        assert_eq!(mirror.march(Vec3::new(2.0, 0.0, 0.0)), -1.0);
+        assert_eq!(mirror.march(Vec3::new(0.0, 0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_elongate() {
+        let capsule = Elongate::new(ball(Vec3::new(0.0, 0.0, 0.0), 1.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(capsule.march(Vec3::new(2.0, 0.0, 0.0)), 0.0);
+        assert_eq!(capsule.march(Vec3::new(-3.0, 0.0, 0.0)), 1.0);
+        assert_eq!(capsule.march(Vec3::new(0.5, 2.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_lipschitz() {
+        let mut rng = StdRng::seed_from_u64(33);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        assert_bounded(&Twist::new(bar(), origin, 1.5, 1.6), &mut rng);
+        assert_bounded(&Bend::new(bar(), origin, 0.8, 1.6), &mut rng);
+        assert_bounded(&Repeat::infinite(ball(origin, 0.4), Vec3::new(1.0, 1.0, 1.0)), &mut rng);
+        assert_bounded(&Mirror::new(bar(), Vec3::new(0.5, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)), &mut rng);
+
+        // without the bound the twist overshoots
+        let twist = Twist::new(bar(), origin, 1.5, 1.6);
+        assert!(twist.lipschitz() > 1.0);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// axis-aligned ellipsoid.
+// there's no exact distance, so this is a bound that's exact near the surface.
+#[derive(Debug, Copy, Clone)]
+pub struct Ellipsoid {
+    pub position: Vec3,
+    pub radii: Vec3,
+    pub material: Material,
+}
+
+impl Ellipsoid {
+    pub fn new(position: Vec3, radii: Vec3, material: Material) -> Ellipsoid {
+        Ellipsoid { position, radii, material }
+    }
+}
+
+impl March for Ellipsoid {
+    fn material(&self) -> Material { self.material }
+
+    // from iquilezles.org/www/articles/ellipsoids/ellipsoids.htm
+    fn march(&self, point: Vec3) -> f64 {
+        let p = point - self.position;
+        let k0 = (p / self.radii).length();
+        let k1 = (p / (self.radii * self.radii)).length();
+        if k1 == 0.0 { return -self.radii.x.min(self.radii.y).min(self.radii.z); }
+        k0 * (k0 - 1.0) / k1
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, self.radii))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Ellipsoid;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let ellipsoid = Ellipsoid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0), Material::sky());
+        // exact along the axes
+        assert_eq!(ellipsoid.march(Vec3::new(2.0, 0.0, 0.0)), 1.0);
+        assert_eq!(ellipsoid.march(Vec3::new(0.0, 0.0, 3.0)), 0.0);
+        assert_eq!(ellipsoid.march(Vec3::new(0.0, 0.0, 0.0)), -1.0);
+
+        // a sphere when the radii match
+        let sphere = Ellipsoid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 2.0), Material::sky());
+        assert!((sphere.march(Vec3::new(3.0, 3.0, 3.0)) - (27.0_f64.sqrt() - 2.0)).abs() < 1e-9);
+    }
+}
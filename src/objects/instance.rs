@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use crate::structures::vec3::Vec3;
+use crate::structures::ray::Ray;
+use crate::structures::material::Material;
+use crate::structures::matrix::Matrix;
+use crate::structures::cast::Cast;
+use crate::structures::aabb::Aabb;
+use crate::objects::trace::Trace;
+use crate::objects::transform::Transformed;
+
+// one placement of a piece of shared geometry.
+// the geometry, usually a bvh, is stored once however many times it's placed,
+// so each instance only costs a transform.
+pub struct Instance {
+    placed: Transformed<Arc<dyn Trace>>,
+    pub material: Option<Material>, // replaces every material in the geometry
+}
+
+impl Instance {
+    // None if the matrix can't be undone
+    pub fn new(geometry: Arc<dyn Trace>, matrix: Matrix, material: Option<Material>) -> Option<Instance> {
+        let placed = Transformed::new(Box::new(geometry), matrix)?;
+        Some(Instance { placed, material })
+    }
+}
+
+impl Trace for Instance {
+    fn material(&self) -> Material {
+        self.material.unwrap_or_else(|| self.placed.material())
+    }
+
+    fn trace(&self, ray: Ray) -> Option<(f64, Vec3)> {
+        self.placed.trace(ray)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.placed.bounds()
+    }
+
+    fn cast(&self, ray: Ray) -> Option<Cast> {
+        let cast = self.placed.cast(ray)?;
+        Some(Cast { material: self.material.unwrap_or(cast.material), ..cast })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::sync::Arc;
+
+    use super::Instance;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::matrix::Matrix;
+    use crate::structures::material::Material;
+    use crate::objects::trace::Trace;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::bvh::Bvh;
+
+    fn red() -> Material { Material::dielectric(Vec3::new(1.0, 0.0, 0.0), 0.5, 0.5) }
+    fn blue() -> Material { Material::dielectric(Vec3::new(0.0, 0.0, 1.0), 0.5, 0.5) }
+
+    #[test]
+    fn test_instances() {
+        let geometry: Arc<dyn Trace> = Arc::new(Bvh::new(vec![
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, red())),
+        ]));
+
+        let instances: Vec<Box<dyn Trace>> = (0..10).map(|i| {
+            let matrix = Matrix::translate(Vec3::new(i as f64 * 10.0, 0.0, 0.0));
+            let material = if i % 2 == 0 { None } else { Some(blue()) };
+            Box::new(Instance::new(geometry.clone(), matrix, material).unwrap()) as Box<dyn Trace>
+        }).collect();
+
+        // the geometry is only stored once
+        assert_eq!(Arc::strong_count(&geometry), 11);
+
+        let down = |x: f64| Ray::new(Vec3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let own = Trace::hit(&instances, down(40.0)).unwrap();
+        let replaced = Trace::hit(&instances, down(50.0)).unwrap();
+
+        assert_eq!((own.distance, own.id), (4.0, 4));
+        assert_eq!(own.material.color, red().color);
+        assert_eq!(replaced.material.color, blue().color);
+        assert!(Trace::hit(&instances, down(45.0)).is_none());
+    }
+}
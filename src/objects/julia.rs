@@ -0,0 +1,100 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+use crate::objects::orbit::OrbitTrap;
+
+// a quaternion, as a real part and three imaginary parts
+type Quaternion = [f64; 4];
+
+fn square(q: Quaternion) -> Quaternion {
+    [
+        q[0] * q[0] - q[1] * q[1] - q[2] * q[2] - q[3] * q[3],
+        2.0 * q[0] * q[1],
+        2.0 * q[0] * q[2],
+        2.0 * q[0] * q[3],
+    ]
+}
+
+fn length_squared(q: Quaternion) -> f64 {
+    q.iter().map(|x| x * x).sum()
+}
+
+// the points that don't escape under z -> z^2 + c,
+// a 3d slice through a 4d quaternion julia set
+pub struct Julia {
+    pub position: Vec3,
+    pub c: [f64; 4], // picks the shape, values near the edge of the mandelbrot set are most detailed
+    pub slice: f64, // the fourth coordinate of the slice
+    pub iterations: usize,
+    pub material: Material,
+    pub coloring: Option<OrbitTrap>,
+}
+
+impl Julia {
+    pub fn new(position: Vec3, c: [f64; 4], iterations: usize, material: Material) -> Julia {
+        Julia { position, c, slice: 0.0, iterations, material, coloring: None }
+    }
+
+    // distance estimate, showing each step of the orbit in the slice to visit.
+    // from iquilezles.org/www/articles/juliasets3d/juliasets3d.htm
+    fn iterate(&self, point: Vec3, mut visit: impl FnMut(Vec3)) -> f64 {
+        let p = point - self.position;
+        let mut z = [p.x, p.y, p.z, self.slice];
+        let mut z2 = length_squared(z);
+        let mut dz2 = 1.0; // squared length of the derivative
+
+        for _ in 0..self.iterations {
+            dz2 *= 4.0 * z2;
+            let s = square(z);
+            z = [s[0] + self.c[0], s[1] + self.c[1], s[2] + self.c[2], s[3] + self.c[3]];
+            z2 = length_squared(z);
+            visit(Vec3::new(z[0], z[1], z[2]));
+            if z2 > 256.0 { break; }
+        }
+
+        // negative for points that shrink towards the middle of the set,
+        // but those that land exactly on it have no estimate
+        let distance = 0.25 * z2.ln() * (z2 / dz2).sqrt();
+        if distance.is_nan() { -1.0 } else { distance }
+    }
+}
+
+impl March for Julia {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.iterate(point, |_| ())
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        OrbitTrap::color(&self.coloring, self.material, self.iterations, |visit| { self.iterate(point, visit); })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // past this radius z^2 + c only grows
+        let reach = (1.0 + (1.0 + 4.0 * length_squared(self.c).sqrt()).sqrt()) / 2.0;
+        Some(Aabb::around(self.position, Vec3::new(reach, reach, reach)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Julia;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        // with c = 0 the set is the unit ball
+        let ball = Julia::new(Vec3::new(0.0, 0.0, 0.0), [0.0; 4], 12, Material::sky());
+        assert!(ball.march(Vec3::new(0.0, 0.0, 0.0)) < 0.0);
+        assert!(ball.march(Vec3::new(0.0, 0.9, 0.0)) < 0.0);
+        let outside = ball.march(Vec3::new(2.0, 0.0, 0.0));
+        assert!(outside > 0.0 && outside <= 1.0);
+
+        let julia = Julia::new(Vec3::new(0.0, 0.0, 0.0), [-0.2, 0.8, 0.0, 0.0], 12, Material::sky());
+        assert!(julia.march(Vec3::new(4.0, 0.0, 0.0)) > 2.0);
+    }
+}
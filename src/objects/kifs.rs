@@ -0,0 +1,86 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::matrix::Matrix;
+use crate::objects::march::March;
+use crate::objects::orbit::OrbitTrap;
+
+// kaleidoscopic iterated function system, after Knighty's fractals.
+// each step mirrors space into one wedge of an octahedron,
+// turns it, then scales it away from offset.
+// with no rotation, a scale of 3 and an offset of (1, 1, 1) this is a menger-like sponge.
+pub struct Kifs {
+    pub position: Vec3,
+    pub scale: f64,
+    pub offset: Vec3,
+    pub rotation: Matrix, // applied every step, should only rotate
+    pub iterations: usize,
+    pub material: Material,
+    pub coloring: Option<OrbitTrap>,
+}
+
+impl Kifs {
+    pub fn new(position: Vec3, scale: f64, offset: Vec3, rotation: Matrix, iterations: usize, material: Material) -> Kifs {
+        Kifs { position, scale, offset, rotation, iterations, material, coloring: None }
+    }
+
+    // distance estimate, showing each step of the orbit to visit
+    fn iterate(&self, point: Vec3, mut visit: impl FnMut(Vec3)) -> f64 {
+        let mut z = point - self.position;
+        let mut scale = 1.0;
+
+        for _ in 0..self.iterations {
+            // mirror into the wedge where x >= y >= z >= 0
+            z = z.abs();
+            if z.x < z.y { z = Vec3::new(z.y, z.x, z.z); }
+            if z.x < z.z { z = Vec3::new(z.z, z.y, z.x); }
+            if z.y < z.z { z = Vec3::new(z.x, z.z, z.y); }
+
+            z = self.rotation.vector(z);
+            z = z * self.scale - self.offset * (self.scale - 1.0);
+            scale *= self.scale;
+            visit(z);
+        }
+
+        // distance to a unit cube at the end
+        let q = z.abs() - 1.0;
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        (outside + q.max_element().min(0.0)) / scale
+    }
+}
+
+impl March for Kifs {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.iterate(point, |_| ())
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        OrbitTrap::color(&self.coloring, self.material, self.iterations, |visit| { self.iterate(point, visit); })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Kifs;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::structures::matrix::Matrix;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let offset = Vec3::new(1.0, 1.0, 1.0);
+        let sponge = Kifs::new(Vec3::new(0.0, 0.0, 0.0), 3.0, offset, Matrix::identity(), 5, Material::sky());
+
+        // the corners are solid, and every corner is the same
+        assert!(sponge.march(Vec3::new(0.98, 0.98, 0.98)) <= 0.0);
+        let point = Vec3::new(0.3, 0.7, -0.2);
+        let turned = Vec3::new(-0.7, 0.2, 0.3);
+        assert!((sponge.march(point) - sponge.march(turned)).abs() < 1e-9);
+
+        // far away it's about as far as the bounding cube
+        let far = sponge.march(Vec3::new(5.0, 0.0, 0.0));
+        assert!(far > 3.9 && far < 5.0, "{}", far);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::objects::march::March;
+use crate::objects::orbit::OrbitTrap;
+
+// folds space in a box and a sphere, over and over.
+// negative scales make rounded, bounded boxes,
+// positive scales make sharp, blocky ones.
+pub struct Mandelbox {
+    pub position: Vec3,
+    pub scale: f64,
+    pub min_radius: f64, // points closer than this are scaled up the most
+    pub fixed_radius: f64, // points out to here are inverted in the sphere
+    pub fold: f64, // half the size of the folding box
+    pub iterations: usize,
+    pub material: Material,
+    pub coloring: Option<OrbitTrap>,
+}
+
+impl Mandelbox {
+    // the usual parameters, for a given scale
+    pub fn new(position: Vec3, scale: f64, iterations: usize, material: Material) -> Mandelbox {
+        Mandelbox {
+            position,
+            scale,
+            min_radius: 0.5,
+            fixed_radius: 1.0,
+            fold: 1.0,
+            iterations,
+            material,
+            coloring: None,
+        }
+    }
+
+    // distance estimate, showing each step of the orbit to visit
+    fn iterate(&self, point: Vec3, mut visit: impl FnMut(Vec3)) -> f64 {
+        let offset = point - self.position;
+        let (min2, fixed2) = (self.min_radius * self.min_radius, self.fixed_radius * self.fixed_radius);
+
+        let mut z = offset;
+        let mut dr = 1.0;
+
+        for _ in 0..self.iterations {
+            // reflect whatever's outside the box back in
+            let fold = |x: f64| x.clamp(-self.fold, self.fold) * 2.0 - x;
+            z = Vec3::new(fold(z.x), fold(z.y), fold(z.z));
+
+            // invert in the sphere, but not too close to the center
+            let r2 = z.length_squared();
+            let inversion = if r2 < min2 { fixed2 / min2 } else if r2 < fixed2 { fixed2 / r2 } else { 1.0 };
+            z = z * inversion;
+            dr *= inversion;
+
+            z = z * self.scale + offset;
+            dr = dr * self.scale.abs() + 1.0;
+            visit(z);
+        }
+
+        z.length() / dr
+    }
+}
+
+impl March for Mandelbox {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.iterate(point, |_| ())
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        OrbitTrap::color(&self.coloring, self.material, self.iterations, |visit| { self.iterate(point, visit); })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Mandelbox;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let mandelbox = Mandelbox::new(Vec3::new(0.0, 0.0, 0.0), -1.5, 12, Material::sky());
+
+        // mirrored on every axis
+        let point = Vec3::new(2.0, 1.0, 0.5);
+        let distance = mandelbox.march(point);
+        assert!(distance > 0.0);
+        assert!((mandelbox.march(-point) - distance).abs() < 1e-9);
+
+        // the distance from far away is no more than to the center
+        assert!(mandelbox.march(Vec3::new(10.0, 0.0, 0.0)) < 10.0);
+        assert!(mandelbox.march(Vec3::new(0.0, 0.0, 0.0)) < 1e-3);
+    }
+}
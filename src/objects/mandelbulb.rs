@@ -2,13 +2,17 @@ use std::f64;
 
 use crate::structures::vec3::Vec3;
 use crate::structures::material::Material;
+use crate::structures::dual::Dual;
+use crate::structures::aabb::Aabb;
 use crate::objects::march::March;
+use crate::objects::orbit::OrbitTrap;
 
 pub struct Mandelbulb {
     pub position: Vec3,
     pub power: f64,
     pub iterations: usize,
     pub material: Material,
+    pub coloring: Option<OrbitTrap>,
 }
 
 fn length(x: f64, y: f64) -> f64 {
@@ -22,14 +26,12 @@ impl Mandelbulb {
             power: power,
             iterations: iterations,
             material: material,
+            coloring: None,
         }
     }
-}
 
-impl March for Mandelbulb {
-    fn material(&self) -> Material { self.material }
-
-    fn march(&self, point: Vec3) -> f64 {
+    // distance estimate, showing each step of the orbit to visit
+    fn iterate(&self, point: Vec3, mut visit: impl FnMut(Vec3)) -> f64 {
         let mut zn = point.clone() - self.position;
         let mut rad = zn.length();
         let mut d = 1.0;
@@ -44,16 +46,120 @@ impl March for Mandelbulb {
 
             let th = length(zn.x, zn.y).atan2(zn.z);
             let phi = zn.y.atan2(zn.x);
-            let rado = rad.powi(8);
-            d = rad.powi(7) * 7.0 * d + 1.0;
+            let rado = rad.powf(self.power);
+            d = rad.powf(self.power - 1.0) * self.power * d + 1.0;
 
             let sint = (th * self.power).sin();
             zn.x = rado * sint * (phi * self.power).cos();
             zn.y = rado * sint * (phi * self.power).sin();
             zn.z = rado * (th * self.power).cos();
             zn = zn + (point - self.position);
+            visit(zn);
         }
 
         return 0.5 * rad.ln() * rad / d;
     }
 }
+
+impl March for Mandelbulb {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.iterate(point, |_| ())
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        OrbitTrap::color(&self.coloring, self.material, self.iterations, |visit| { self.iterate(point, visit); })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // points further than 2 from the middle escape straight away
+        Some(Aabb::around(self.position, Vec3::new(2.0, 2.0, 2.0)))
+    }
+
+    // the estimate is run on dual numbers,
+    // which track how each step depends on the starting point
+    fn gradient(&self, point: Vec3) -> Option<Vec3> {
+        let p = point - self.position;
+        if p.length() > 2.5 { return Some(p); }
+
+        let c = [
+            Dual::new(p.x, Vec3::new(1.0, 0.0, 0.0)),
+            Dual::new(p.y, Vec3::new(0.0, 1.0, 0.0)),
+            Dual::new(p.z, Vec3::new(0.0, 0.0, 1.0)),
+        ];
+        let [mut x, mut y, mut z] = c;
+        let mut rad = (x * x + y * y + z * z).sqrt();
+        let mut d = Dual::new(1.0, Vec3::new(0.0, 0.0, 0.0));
+
+        for _ in 0..self.iterations {
+            rad = (x * x + y * y + z * z).sqrt();
+            if rad.value > 2.0 { break; }
+
+            let th = (x * x + y * y).sqrt().atan2(z);
+            let phi = y.atan2(x);
+            let rado = rad.powf(self.power);
+            d = rad.powf(self.power - 1.0) * d * self.power + 1.0;
+
+            let sint = (th * self.power).sin();
+            x = rado * sint * (phi * self.power).cos() + c[0];
+            y = rado * sint * (phi * self.power).sin() + c[1];
+            z = rado * (th * self.power).cos() + c[2];
+        }
+
+        // orbits that land on the axis have no angle, and so no gradient
+        let gradient = (rad.ln() * rad / d).gradient;
+        if gradient.length() > 0.0 { Some(gradient) } else { None }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Mandelbulb;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::structures::ray::Ray;
+    use crate::objects::march::{March, Marching};
+
+    #[test]
+    fn test_gradient() {
+        // the dual number gradient is the slope of the estimate itself,
+        // checked against finely spaced samples on the surface
+        let bulb = Mandelbulb::new(Vec3::new(0.0, 0.0, 0.0), 8.0, 8, Material::sky());
+        let march: Vec<Box<dyn March>> = vec![Box::new(Mandelbulb::new(Vec3::new(0.0, 0.0, 0.0), 8.0, 8, Material::sky()))];
+        let (mut total, mut count) = (0.0, 0);
+
+        for i in 0..200 {
+            let angle = i as f64 * 0.1;
+            let origin = Vec3::new(angle.cos() * 3.0, (angle * 0.37).sin() * 2.0, angle.sin() * 3.0);
+            let ray = Ray::new(origin, (-origin).unit());
+            let cast = match March::hit(&march, ray, &Marching::new()) { Some(cast) => cast, None => continue };
+
+            let p = ray.point_at(&cast.distance);
+            let h = 1e-7;
+            let slope = |axis: Vec3| bulb.march(p + axis * h) - bulb.march(p - axis * h);
+            let sampled = Vec3::new(
+                slope(Vec3::new(1.0, 0.0, 0.0)), slope(Vec3::new(0.0, 1.0, 0.0)), slope(Vec3::new(0.0, 0.0, 1.0)),
+            ).unit();
+
+            total += bulb.gradient(p).unwrap().unit().dot(&sampled);
+            count += 1;
+        }
+
+        assert!(count > 150);
+        assert!(total / count as f64 > 0.99, "{}", total / count as f64);
+    }
+
+    #[test]
+    fn test_power() {
+        // one step from (0, 0, 1.5) escapes, which can be worked out by hand
+        let point = Vec3::new(0.0, 0.0, 1.5);
+
+        for &power in [2.0, 3.0, 8.0].iter() {
+            let bulb = Mandelbulb::new(Vec3::new(0.0, 0.0, 0.0), power, 8, Material::sky());
+            let rad = 1.5_f64.powf(power) + 1.5;
+            let d = power * 1.5_f64.powf(power - 1.0) + 1.0;
+            assert!((bulb.march(point) - 0.5 * rad.ln() * rad / d).abs() < 1e-9);
+        }
+    }
+}
@@ -1,58 +1,555 @@
+use std::cmp::Ordering;
+
 use crate::structures::material::Material;
 use crate::structures::cast::Cast;
 use crate::structures::vec3::Vec3;
 use crate::structures::ray::Ray;
+use crate::structures::aabb::Aabb;
+use crate::structures::stats;
 
 use crate::render::EPSILON;
-pub const MAX_STEPS: usize = 64;
-pub const MAX_DEPTH: f64 = 40.0;
+
+// how rays are marched through a scene
+#[derive(Debug, Copy, Clone)]
+pub struct Marching {
+    pub max_steps: usize,
+    pub max_depth: f64, // rays that get this far without hitting anything miss
+    pub epsilon: f64, // how close a ray has to get to hit, up close
+    pub footprint: f64, // radius of a pixel one unit from the camera, set when rendering.
+                        // far away hits only need to be a pixel close, so the threshold grows with depth
+    pub relaxation: f64, // steps are lengthened by this much, between 1 and 2, backing off when it overshoots
+    pub refine: usize, // bisection steps to pin down a surface the ray stepped through
+    pub analytic: bool, // take normals from objects' own gradients where they have them
+}
+
+impl Marching {
+    pub fn new() -> Marching {
+        Marching {
+            max_steps: 128,
+            max_depth: 100.0,
+            epsilon: EPSILON,
+            footprint: 0.0,
+            relaxation: 1.2,
+            refine: 8,
+            analytic: true,
+        }
+    }
+
+    // hit threshold at a depth along the ray
+    pub fn threshold(&self, depth: f64) -> f64 {
+        self.epsilon.max(self.footprint * depth)
+    }
+}
+
+// the stretch of a ray each object could be on, in order,
+// or that comes within pad of it. ignored objects are left out.
+struct Spans(Vec<(f64, f64, usize)>);
+
+impl Spans {
+    fn new(march: &[Box<dyn March>], ray: Ray, pad: f64, ignore: Option<usize>) -> Spans {
+        let mut spans: Vec<(f64, f64, usize)> = march.iter().enumerate().filter_map(|(id, object)| {
+            if Some(id) == ignore { return None; }
+            match object.bounds() {
+                Some(bounds) => bounds.pad(pad).span(ray).map(|(enter, exit)| (enter, exit, id)),
+                None => Some((0.0, f64::INFINITY, id)),
+            }
+        }).collect();
+        spans.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        Spans(spans)
+    }
+
+    // objects that could be at a depth
+    fn near(&self, depth: f64) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().filter(move |s| s.0 <= depth && depth <= s.1).map(|s| s.2)
+    }
+
+    // where the next object after a depth could start
+    fn ahead(&self, depth: f64) -> Option<f64> {
+        self.0.iter().map(|s| s.0).find(|&enter| enter > depth)
+    }
+}
 
 pub trait March: Send + Sync {
     fn material(&self) -> Material;
     fn march(&self, point: Vec3) -> f64; // distance to nearest point
+
+    // material at a point on the surface, for objects that vary
+    fn material_at(&self, _point: Vec3) -> Material { self.material() }
+
+    // how many times faster than the true distance march can change.
+    // operators that stretch space overestimate distances by up to this much.
+    fn lipschitz(&self) -> f64 { 1.0 }
+
+    // distance that's always safe to step
+    fn bound(&self, point: Vec3) -> f64 { self.march(point) / self.lipschitz() }
+
+    // box the whole surface fits in, None if it goes on forever
+    // or is too hard to pin down. rays only march an object inside its box.
+    fn bounds(&self) -> Option<Aabb> { None }
+
+    // exact direction the distance grows fastest in, for objects that know it.
+    // needn't be normalized.
+    fn gradient(&self, _point: Vec3) -> Option<Vec3> { None }
 }
 
 impl dyn March {
-    fn sdf(point: Vec3, march: &Vec<Box<dyn March>>) -> (f64, Material) {
+    // distance to, and index of, the nearest of some of the objects
+    fn nearest(point: Vec3, march: &[Box<dyn March>], ids: impl Iterator<Item = usize>) -> (f64, usize) {
         let mut min = f64::MAX;
-        let mut mat = Material::sky();
+        let mut id = 0;
 
-        for object in march.iter() {
-            let distance = object.march(point);
+        for index in ids {
+            let distance = march[index].bound(point);
 
             if distance <= min {
                 min = distance;
-                mat = object.material();
+                id = index;
             }
         }
 
-        return (min, mat);
+        return (min, id);
     }
 
-    // TODO: replace with faster normal epsilon sample technique
-    fn normal(p: Vec3, march: &Vec<Box<dyn March>>) -> Vec3 {
-        Vec3::new(
-            March::sdf(Vec3::new(p.x + EPSILON, p.y, p.z), march).0 - March::sdf(Vec3::new(p.x - EPSILON, p.y, p.z), march).0,
-            March::sdf(Vec3::new(p.x, p.y + EPSILON, p.z), march).0 - March::sdf(Vec3::new(p.x, p.y - EPSILON, p.z), march).0,
-            March::sdf(Vec3::new(p.x, p.y, p.z + EPSILON), march).0 - March::sdf(Vec3::new(p.x, p.y, p.z - EPSILON), march).0,
-        ).unit()
+    // surface normal of one object, from its own gradient if it has one
+    // and allowed, otherwise from four samples h apart at the corners of a tetrahedron
+    fn normal(object: &dyn March, point: Vec3, h: f64, analytic: bool) -> Vec3 {
+        if analytic {
+            if let Some(gradient) = object.gradient(point) { return gradient.unit(); }
+        }
+
+        let corners = [
+            Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0),
+        ];
+
+        corners.iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, k| sum + *k * object.march(point + *k * h))
+            .unit()
     }
 
-    pub fn hit(march: &Vec<Box<dyn March>>, ray: Ray) -> Option<Cast> {
-        let mut depth = EPSILON;
+    // enhanced sphere tracing, after keinert et al. 2014.
+    // steps are over-relaxed; when the spheres at either end of a step
+    // don't overlap the ray may have skipped a surface, so it goes back
+    // and carries on with plain steps.
+    // only objects whose bounds the ray is inside are evaluated,
+    // and the empty space between bounds is skipped entirely.
+    pub fn hit(march: &Vec<Box<dyn March>>, ray: Ray, settings: &Marching) -> Option<Cast> {
+        let spans = Spans::new(march, ray, 0.0, None);
+        let mut depth = settings.epsilon;
+
+        // rays leaving the inside of an object march towards its surface
+        let side = March::nearest(ray.point_at(&depth), march, spans.near(depth)).0.signum();
+        let distance_at = |depth: f64| {
+            let (distance, id) = March::nearest(ray.point_at(&depth), march, spans.near(depth));
+            // nothing nearby is outside no matter which side the ray started on
+            if distance == f64::MAX { (distance, id) } else { (distance * side, id) }
+        };
+
+        let mut relaxation = settings.relaxation;
+        let mut last = (depth, 0.0); // depth and distance at the last point outside
+
+        let mut steps = 0;
+        let mut exhausted = true;
+
+        for _step in 0..settings.max_steps {
+            steps += 1;
 
-        for _step in 0..MAX_STEPS {
-            let point = ray.point_at(&depth);
-            let (distance, material) = March::sdf(point, march);
+            // the nearest the surface can be is already too far
+            if last.0 + last.1 >= settings.max_depth { exhausted = false; break; }
 
-            if distance <= EPSILON {
-                let normal = March::normal(point, march);
-                return Some(Cast { distance: depth, normal, material });
+            // outside every box, so jump to the next one
+            if spans.near(depth).next().is_none() {
+                match spans.ahead(depth) {
+                    Some(enter) => { depth = enter; last = (enter, 0.0); continue; },
+                    None => { exhausted = false; break; },
+                }
             }
 
-            if distance >= MAX_DEPTH { break; }
-            depth += distance;
+            let (distance, id) = distance_at(depth);
+            let step = depth - last.0;
+
+            if relaxation > 1.0 && step > 0.0 && distance.abs() + last.1 < step {
+                relaxation = 1.0;
+                depth = last.0 + last.1;
+                continue;
+            }
+
+            if distance <= settings.threshold(depth) {
+                // stepped through the surface, so bisect back to it
+                let (mut outside, mut inside) = (last.0, depth);
+                if distance < 0.0 && settings.refine > 0 {
+                    for _ in 0..settings.refine {
+                        let middle = (outside + inside) * 0.5;
+                        if distance_at(middle).0 > 0.0 { outside = middle; } else { inside = middle; }
+                    }
+                    depth = outside;
+                }
+
+                let point = ray.point_at(&depth);
+                let id = if depth != inside { distance_at(depth).1 } else { id };
+                let normal = March::normal(&*march[id], point, settings.threshold(depth), settings.analytic);
+                let material = march[id].material_at(point);
+                stats::record(|stats| stats.march(steps, false));
+                return Some(Cast { distance: depth, normal, material, id });
+            }
+
+            last = (depth, distance);
+            depth += distance * relaxation;
+
+            // objects whose boxes start part way through the step
+            // haven't been checked, so stop at the first of them
+            if let Some(enter) = spans.ahead(last.0) { depth = depth.min(enter); }
         }
+
+        stats::record(|stats| stats.march(steps, exhausted));
         return None;
     }
+
+    // how much light gets along a ray from one point to another max away, from 0 to 1.
+    // rays that pass close to something are partly shadowed, giving a soft penumbra;
+    // the larger the hardness, the narrower it is.
+    // closest approach is estimated between samples, after iquilezles.org/articles/rmshadows.
+    // the ignored object, usually the light itself, casts no shadow.
+    pub fn shadow(
+        march: &Vec<Box<dyn March>>, ray: Ray, max: f64, hardness: f64,
+        ignore: Option<usize>, settings: &Marching,
+    ) -> f64 {
+        // rays can be shadowed by objects they pass this close to
+        let spans = Spans::new(march, ray, max / hardness, ignore);
+        let mut light: f64 = 1.0;
+        let mut depth = settings.epsilon;
+        let mut previous = f64::MAX;
+
+        for _step in 0..settings.max_steps {
+            if depth >= max { break; }
+
+            if spans.near(depth).next().is_none() {
+                match spans.ahead(depth) {
+                    Some(enter) => { depth = enter; previous = f64::MAX; continue; },
+                    None => break,
+                }
+            }
+
+            let (distance, _) = March::nearest(ray.point_at(&depth), march, spans.near(depth));
+            if distance < settings.threshold(depth) { return 0.0; }
+
+            // where the spheres from this sample and the last meet,
+            // and how far that is from the ray
+            let along = distance * distance / (2.0 * previous);
+            let closest = (distance * distance - along * along).max(0.0).sqrt();
+            light = light.min(hardness * closest / (depth - along).max(settings.epsilon));
+
+            previous = distance;
+            let next = depth + distance;
+            depth = spans.ahead(depth).map_or(next, |enter| next.min(enter));
+        }
+
+        light.clamp(0.0, 1.0)
+    }
+
+    // how much of the sky a surface point sees, from 0 to 1,
+    // from how far the surface is from anything else at a few points along its normal
+    pub fn occlusion(march: &Vec<Box<dyn March>>, point: Vec3, normal: Vec3, spacing: f64, samples: usize) -> f64 {
+        let (mut occluded, mut most) = (0.0, 0.0);
+        let mut weight = 1.0;
+
+        for i in 1..=samples {
+            let offset = spacing * i as f64;
+            let sample = point + normal * offset;
+
+            // objects whose boxes are further away can't be any closer
+            let near = (0..march.len()).filter(|id| {
+                march[*id].bounds().map_or(true, |bounds| bounds.pad(offset).contains(sample))
+            });
+            let distance = March::nearest(sample, march, near).0.min(offset);
+
+            occluded += (offset - distance).max(0.0) * weight;
+            most += offset * weight;
+            weight *= 0.5;
+        }
+
+        if most == 0.0 { return 1.0; }
+        1.0 - occluded / most
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::{March, Marching};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::material::Material;
+    use crate::structures::matrix::Matrix;
+    use crate::structures::aabb::Aabb;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::cuboid::Cuboid;
+    use crate::objects::torus::Torus;
+    use crate::objects::capsule::Capsule;
+    use crate::objects::cylinder::Cylinder;
+    use crate::objects::cone::Cone;
+    use crate::objects::ellipsoid::Ellipsoid;
+    use crate::objects::prism::Prism;
+    use crate::objects::mandelbulb::Mandelbulb;
+    use crate::objects::menger::Menger;
+    use crate::objects::sierpinski::Sierpinski;
+    use crate::objects::julia::Julia;
+    use crate::objects::combine::{Union, Intersection, Difference, Blend};
+    use crate::objects::domain::{Repeat, Mirror, Twist, Elongate};
+    use crate::objects::surface::{Round, Onion, Displace};
+    use crate::objects::transform::Transformed;
+
+    // claims to be three times further away than it is
+    struct Overestimate(Box<dyn March>);
+
+    impl March for Overestimate {
+        fn material(&self) -> Material { self.0.material() }
+        fn march(&self, point: Vec3) -> f64 { self.0.march(point) * 3.0 }
+    }
+
+    // hides an object's bounds, so it's evaluated everywhere
+    struct Unbounded(Box<dyn March>);
+
+    impl March for Unbounded {
+        fn material(&self) -> Material { self.0.material() }
+        fn march(&self, point: Vec3) -> f64 { self.0.march(point) }
+    }
+
+    // keeps track of how many times it's evaluated
+    struct Counted(Box<dyn March>, Arc<AtomicUsize>);
+
+    impl March for Counted {
+        fn material(&self) -> Material { self.0.material() }
+        fn march(&self, point: Vec3) -> f64 { self.1.fetch_add(1, Ordering::Relaxed); self.0.march(point) }
+        fn bounds(&self) -> Option<Aabb> { self.0.bounds() }
+    }
+
+    fn point(rng: &mut StdRng) -> Vec3 {
+        Vec3::new(rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5) * 16.0
+    }
+
+    fn forward() -> Ray {
+        Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    fn sphere(z: f64) -> Sphere {
+        Sphere::new(Vec3::new(0.0, 0.0, z), 1.0, Material::sky())
+    }
+
+    #[test]
+    fn test_far() {
+        let march: Vec<Box<dyn March>> = vec![Box::new(sphere(-60.0))];
+        let cast = March::hit(&march, forward(), &Marching::new()).unwrap();
+        assert!((cast.distance - 59.0).abs() < 1e-3);
+
+        let near = Marching { max_depth: 40.0, ..Marching::new() };
+        assert!(March::hit(&march, forward(), &near).is_none());
+    }
+
+    #[test]
+    fn test_thin() {
+        // a sheet barely thicker than the hit threshold, with a long run-up
+        let sheet = Cuboid::new(Vec3::new(0.0, 0.0, -20.0), Vec3::new(5.0, 5.0, 0.001), Material::sky());
+        let march: Vec<Box<dyn March>> = vec![Box::new(sheet)];
+
+        for &relaxation in [1.0, 1.5, 1.9].iter() {
+            let settings = Marching { relaxation, ..Marching::new() };
+            let cast = March::hit(&march, forward(), &settings).unwrap();
+            assert!((cast.distance - 19.999).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_refine() {
+        // a wall whose front face is at z = -4, which the first step passes
+        let wall = Cuboid::new(Vec3::new(0.0, 0.0, -10.0), Vec3::new(50.0, 50.0, 6.0), Material::sky());
+        let march: Vec<Box<dyn March>> = vec![Box::new(Overestimate(Box::new(wall)))];
+        let settings = Marching { relaxation: 1.0, refine: 20, analytic: false, ..Marching::new() };
+        let cast = March::hit(&march, forward(), &settings).unwrap();
+        assert!((cast.distance - 4.0).abs() < 1e-4);
+        assert!((cast.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_footprint() {
+        // a wider footprint settles for hits further off the surface,
+        // which a ray grazing the sphere approaches slowly
+        let march: Vec<Box<dyn March>> = vec![Box::new(sphere(-30.0))];
+        let ray = Ray::new(Vec3::new(0.0, 0.9, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let exact = 30.0 - 0.19_f64.sqrt();
+
+        let settings = Marching { footprint: 0.01, ..Marching::new() };
+        let cast = March::hit(&march, ray, &settings).unwrap();
+        let distance = sphere(-30.0).march(ray.point_at(&cast.distance));
+        assert!(cast.distance < exact - 0.01);
+        assert!(distance > 0.0 && distance <= settings.threshold(cast.distance));
+
+        let cast = March::hit(&march, ray, &Marching::new()).unwrap();
+        assert!((cast.distance - exact).abs() < 1e-2);
+    }
+
+    fn bounded() -> Vec<Box<dyn March>> {
+        let m = Material::sky();
+        let o = Vec3::new(0.0, 0.0, 0.0);
+        let ball = |x: f64| Box::new(Sphere::new(Vec3::new(x, 0.0, 0.0), 1.0, m));
+        let bar = || Box::new(Cuboid::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.5, 2.0, 0.3), m));
+        let turn = Matrix::translate(Vec3::new(2.0, 1.0, 0.0)) * Matrix::rotate(Vec3::new(1.0, 1.0, 0.0), PI / 3.0);
+
+        vec![
+            Box::new(Cuboid::rounded(o, Vec3::new(1.0, 2.0, 3.0), 0.5, m)),
+            Box::new(Torus::new(o, 3.0, 0.5, m)),
+            Box::new(Capsule::new(Vec3::new(-1.0, 2.0, 0.0), Vec3::new(3.0, -1.0, 1.0), 0.7, m)),
+            Box::new(Cylinder::new(o, 2.0, 1.0, m)),
+            Box::new(Cone::new(o, 2.0, 1.5, 0.5, m)),
+            Box::new(Ellipsoid::new(o, Vec3::new(3.0, 1.0, 2.0), m)),
+            Box::new(Prism::new(o, 2.0, 1.0, m)),
+            Box::new(Mandelbulb::new(o, 8.0, 6, m)),
+            Box::new(Menger::new(o, 2.0, 3, m)),
+            Box::new(Sierpinski::new(o, 2.0, 6, m)),
+            Box::new(Julia::new(o, [-0.2, 0.8, 0.0, 0.0], 8, m)),
+            Box::new(Union::new(ball(-1.0), ball(1.5), Blend::Polynomial(1.0))),
+            Box::new(Union::new(ball(-1.0), ball(1.5), Blend::Exponential(0.3))),
+            Box::new(Intersection::new(ball(0.0), bar(), Blend::Hard)),
+            Box::new(Difference::new(bar(), ball(0.0), Blend::Hard)),
+            Box::new(Repeat::bounded(ball(0.0), Vec3::new(3.0, 0.0, 2.5), Vec3::new(1.0, 0.0, 2.0))),
+            Box::new(Mirror::new(ball(2.0), Vec3::new(0.5, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0))),
+            Box::new(Twist::new(bar(), o, 0.5, 3.0)),
+            Box::new(Elongate::new(ball(0.0), o, Vec3::new(2.0, 0.0, 1.0))),
+            Box::new(Round::new(bar(), 0.5)),
+            Box::new(Onion::new(ball(0.0), 0.2)),
+            Box::new(Displace::new(ball(0.0), 0.3, 2.0)),
+            Box::new(Transformed::new(bar(), turn).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut rng = StdRng::seed_from_u64(39);
+
+        for object in bounded() {
+            let bounds = object.bounds().unwrap().pad(1e-9);
+            for _ in 0..20000 {
+                let p = point(&mut rng);
+                assert!(bounds.contains(p) || object.march(p) > 0.0, "{:?} outside {:?}", p, bounds);
+            }
+        }
+
+        let infinite = Repeat::infinite(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, Material::sky())), Vec3::new(3.0, 0.0, 0.0));
+        assert!(infinite.bounds().is_none());
+    }
+
+    #[test]
+    fn test_bounded_matches_unbounded() {
+        let mut rng = StdRng::seed_from_u64(40);
+        let scene = |rng: &mut StdRng, hide: bool| -> Vec<Box<dyn March>> {
+            (0..20).map(|i| {
+                let center = point(rng) * 0.5;
+                let object: Box<dyn March> = if i % 2 == 0 {
+                    Box::new(Sphere::new(center, 0.3 + rng.gen::<f64>(), Material::sky()))
+                } else {
+                    Box::new(Torus::new(center, 1.0, 0.2 + rng.gen::<f64>() * 0.3, Material::sky()))
+                };
+                if hide { Box::new(Unbounded(object)) } else { object }
+            }).collect()
+        };
+
+        let bounded = scene(&mut StdRng::seed_from_u64(41), false);
+        let unbounded = scene(&mut StdRng::seed_from_u64(41), true);
+        let settings = Marching { relaxation: 1.0, max_steps: 512, ..Marching::new() };
+
+        for _ in 0..500 {
+            let ray = Ray::new(point(&mut rng), point(&mut rng).unit());
+            match (March::hit(&bounded, ray, &settings), March::hit(&unbounded, ray, &settings)) {
+                (None, None) => (),
+                (Some(a), Some(b)) => {
+                    assert!((a.distance - b.distance).abs() < 1e-3);
+                    assert_eq!(a.id, b.id);
+                },
+                (a, b) => panic!("bounded and unbounded disagree: {:?} {:?}", a.map(|c| c.distance), b.map(|c| c.distance)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_skips_distant() {
+        // the ray hits one ball and passes well wide of the other
+        let near = Sphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0, Material::sky());
+        let aside = Sphere::new(Vec3::new(5.0, 0.0, -5.0), 1.0, Material::sky());
+        let (hit, missed) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let march: Vec<Box<dyn March>> = vec![
+            Box::new(Counted(Box::new(near), hit.clone())),
+            Box::new(Counted(Box::new(aside), missed.clone())),
+        ];
+
+        let cast = March::hit(&march, forward(), &Marching::new()).unwrap();
+        assert!((cast.distance - 9.0).abs() < 1e-3);
+        assert_eq!(cast.id, 0);
+
+        // nor is it sampled for the normal
+        assert!(hit.load(Ordering::Relaxed) > 0);
+        assert_eq!(missed.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_normals() {
+        let mut rng = StdRng::seed_from_u64(41);
+        let cuboid = || Box::new(Cuboid::rounded(Vec3::new(0.5, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0), 0.3, Material::sky()));
+        let turn = Matrix::rotate(Vec3::new(0.0, 1.0, 1.0), 1.0) * Matrix::scale(Vec3::new(1.0, 2.0, 0.5));
+        let objects: Vec<Box<dyn March>> = vec![
+            Box::new(Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0, Material::sky())),
+            cuboid(),
+            Box::new(Transformed::new(cuboid(), turn).unwrap()),
+        ];
+
+        for object in objects.iter() {
+            for _ in 0..1000 {
+                let p = point(&mut rng);
+                // the distance to a box has creases inside it
+                if object.march(p) < 0.01 { continue; }
+
+                let exact = March::normal(&**object, p, 1e-6, true);
+                let sampled = March::normal(&**object, p, 1e-6, false);
+                assert!(exact.dot(&sampled) > 0.9999, "{:?} {:?}", exact, sampled);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shadow() {
+        let march: Vec<Box<dyn March>> = vec![Box::new(sphere(-5.0))];
+        let settings = Marching::new();
+        // from the floor beneath the ball, up to a light above it, passing x away
+        let light = |x: f64| {
+            let ray = Ray::new(Vec3::new(x, -5.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+            March::shadow(&march, ray, 10.0, 8.0, None, &settings)
+        };
+
+        assert_eq!(light(0.0), 0.0);
+        assert_eq!(light(3.0), 1.0);
+        assert!(light(1.05) > 0.0 && light(1.05) < light(1.2) && light(1.2) < 1.0);
+
+        // the light is in front of the ball
+        let short = Ray::new(Vec3::new(0.0, -5.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(March::shadow(&march, short, 3.0, 8.0, None, &settings), 1.0);
+        assert_eq!(March::shadow(&march, short, 10.0, 8.0, Some(0), &settings), 1.0);
+    }
+
+    #[test]
+    fn test_occlusion() {
+        // a floor with a wall standing on it
+        let floor = Cuboid::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(10.0, 1.0, 10.0), Material::sky());
+        let wall = Cuboid::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.1, 2.0, 10.0), Material::sky());
+        let march: Vec<Box<dyn March>> = vec![Box::new(floor), Box::new(wall)];
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let seen = |x: f64| March::occlusion(&march, Vec3::new(x, 0.0, 0.0), up, 0.1, 5);
+
+        assert!((seen(5.0) - 1.0).abs() < 1e-9);
+        assert!(seen(0.15) < seen(0.3) && seen(0.3) < seen(1.0));
+        assert!(seen(0.15) < 0.8);
+    }
 }
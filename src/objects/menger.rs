@@ -0,0 +1,84 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+use crate::objects::orbit::OrbitTrap;
+
+// a cube with crosses carved out of it, then out of each smaller cube, and so on
+pub struct Menger {
+    pub position: Vec3, // center
+    pub size: f64, // half the length of each side
+    pub iterations: usize,
+    pub material: Material,
+    pub coloring: Option<OrbitTrap>,
+}
+
+impl Menger {
+    pub fn new(position: Vec3, size: f64, iterations: usize, material: Material) -> Menger {
+        Menger { position, size, iterations, material, coloring: None }
+    }
+
+    // distance estimate, showing the position in each smaller cube to visit.
+    // from iquilezles.org/www/articles/menger/menger.htm,
+    // worked out for a unit cube and scaled up
+    fn iterate(&self, point: Vec3, mut visit: impl FnMut(Vec3)) -> f64 {
+        let p = (point - self.position) / self.size;
+
+        let q = p.abs() - 1.0;
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        let mut distance = outside + q.max_element().min(0.0);
+        let mut scale = 1.0;
+
+        for _ in 0..self.iterations {
+            // position within each of the smaller cubes
+            let cell = |x: f64| (x * scale).rem_euclid(2.0) - 1.0;
+            let a = Vec3::new(cell(p.x), cell(p.y), cell(p.z));
+            scale *= 3.0;
+            visit(a);
+
+            // the cross through the middle of each cube
+            let r = (1.0 - (a.abs() * 3.0)).abs();
+            let (da, db, dc) = (r.x.max(r.y), r.y.max(r.z), r.z.max(r.x));
+            let cross = (da.min(db).min(dc) - 1.0) / scale;
+            distance = distance.max(cross);
+        }
+
+        distance * self.size
+    }
+}
+
+impl March for Menger {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.iterate(point, |_| ())
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        OrbitTrap::color(&self.coloring, self.material, self.iterations, |visit| { self.iterate(point, visit); })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, Vec3::new(self.size, self.size, self.size)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Menger;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let menger = Menger::new(Vec3::new(0.0, 0.0, 0.0), 2.0, 4, Material::sky());
+
+        // the same as the cube from outside
+        assert!((menger.march(Vec3::new(5.0, 0.0, 0.0)) - 3.0).abs() < 1e-9);
+        // the corners are solid
+        assert!(menger.march(Vec3::new(1.9, 1.9, 1.9)) < 0.0);
+        // the middle is hollow, a third of the way to the faces
+        assert!((menger.march(Vec3::new(0.0, 0.0, 0.0)) - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
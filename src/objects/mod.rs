@@ -5,3 +5,22 @@ pub mod sphere;
 pub mod plane;
 pub mod mandelbulb;
 pub mod triangle;
+pub mod combine;
+pub mod cuboid;
+pub mod torus;
+pub mod capsule;
+pub mod cylinder;
+pub mod cone;
+pub mod ellipsoid;
+pub mod prism;
+pub mod domain;
+pub mod surface;
+pub mod transform;
+pub mod bvh;
+pub mod instance;
+pub mod mandelbox;
+pub mod menger;
+pub mod sierpinski;
+pub mod julia;
+pub mod kifs;
+pub mod orbit;
@@ -0,0 +1,167 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::gradient::Gradient;
+use crate::structures::material::Material;
+
+// what a fractal's orbit is measured against
+#[derive(Debug, Copy, Clone)]
+pub enum Trap {
+    Origin,
+    Plane(Vec3), // through the origin, with this normal
+    Point(Vec3),
+    // how many steps the orbit stayed within this radius, rather than where it went.
+    // most surface points never escape for good, so pick a radius they pass through
+    Iterations(f64),
+}
+
+// colors a fractal by how close the orbit of each surface point
+// comes to a trap, looked up in a gradient
+#[derive(Debug, Clone)]
+pub struct OrbitTrap {
+    pub trap: Trap,
+    pub gradient: Gradient,
+    pub scale: f64, // trap distance that maps to the end of the gradient
+}
+
+// running record of an orbit, fed one point per iteration
+#[derive(Debug, Copy, Clone)]
+pub struct Orbit {
+    trap: Trap,
+    closest: f64,
+    steps: usize,
+    escaped: bool,
+}
+
+impl Orbit {
+    pub fn visit(&mut self, z: Vec3) {
+        let distance = match self.trap {
+            Trap::Origin => z.length(),
+            Trap::Plane(normal) => z.dot(&normal.unit()).abs(),
+            Trap::Point(point) => (z - point).length(),
+            Trap::Iterations(radius) => {
+                self.escaped = self.escaped || z.length() > radius;
+                0.0
+            },
+        };
+
+        self.closest = self.closest.min(distance);
+        if !self.escaped { self.steps += 1; }
+    }
+}
+
+impl OrbitTrap {
+    pub fn new(trap: Trap, gradient: Gradient) -> OrbitTrap {
+        OrbitTrap { trap, gradient, scale: 1.0 }
+    }
+
+    pub fn orbit(&self) -> Orbit {
+        Orbit { trap: self.trap, closest: f64::MAX, steps: 0, escaped: false }
+    }
+
+    // material for an orbit out of at most iterations steps
+    pub fn material(&self, base: Material, orbit: &Orbit, iterations: usize) -> Material {
+        let t = match self.trap {
+            Trap::Iterations(_) => orbit.steps as f64 / iterations.max(1) as f64,
+            _ => orbit.closest / self.scale,
+        };
+
+        Material { color: self.gradient.at(t), ..base }
+    }
+
+    // the material of a fractal at a point, colored if it has a trap.
+    // iterate runs the fractal's iteration, calling visit on each point of the orbit
+    pub fn color(
+        coloring: &Option<OrbitTrap>, base: Material, iterations: usize,
+        iterate: impl FnOnce(&mut dyn FnMut(Vec3)),
+    ) -> Material {
+        let coloring = match coloring {
+            Some(coloring) => coloring,
+            None => return base,
+        };
+
+        let mut orbit = coloring.orbit();
+        iterate(&mut |z| orbit.visit(z));
+        coloring.material(base, &orbit, iterations)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{OrbitTrap, Trap};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::gradient::Gradient;
+    use crate::structures::material::Material;
+    use crate::structures::matrix::Matrix;
+    use crate::objects::march::March;
+    use crate::objects::mandelbox::Mandelbox;
+    use crate::objects::menger::Menger;
+    use crate::objects::sierpinski::Sierpinski;
+    use crate::objects::kifs::Kifs;
+
+    fn ramp() -> Gradient {
+        Gradient::new(vec![(0.0, Vec3::new(0.0, 0.0, 0.0)), (1.0, Vec3::new(1.0, 1.0, 1.0))])
+    }
+
+    fn color(trap: Trap, orbit: &[Vec3]) -> f64 {
+        let coloring = OrbitTrap::new(trap, ramp());
+        let mut record = coloring.orbit();
+        for z in orbit.iter() { record.visit(*z); }
+        coloring.material(Material::sky(), &record, 4).color.x
+    }
+
+    #[test]
+    fn test_traps() {
+        let orbit = [Vec3::new(0.0, 0.8, 0.0), Vec3::new(0.5, 0.0, 0.3), Vec3::new(2.0, 2.0, 2.0)];
+
+        assert!((color(Trap::Origin, &orbit) - 0.34_f64.sqrt()).abs() < 1e-9);
+        assert!(color(Trap::Plane(Vec3::new(0.0, 2.0, 0.0)), &orbit).abs() < 1e-9);
+        assert!((color(Trap::Point(Vec3::new(2.0, 2.0, 2.5)), &orbit) - 0.5).abs() < 1e-9);
+        assert!((color(Trap::Iterations(10.0), &orbit) - 0.75).abs() < 1e-9);
+        assert!((color(Trap::Iterations(2.0), &orbit) - 0.5).abs() < 1e-9);
+    }
+
+    // where a ray coming in from far away along direction meets the surface
+    fn surface(fractal: &dyn March, direction: Vec3) -> Vec3 {
+        let direction = direction.unit();
+        let mut point = direction * 8.0;
+        for _ in 0..1000 {
+            let distance = fractal.march(point);
+            if distance < 1e-6 { break; }
+            point = point - direction * distance;
+        }
+        point
+    }
+
+    #[test]
+    fn test_iterations() {
+        let offset = Vec3::new(1.0, 1.0, 1.0);
+        let mut fractals: Vec<(&str, Box<dyn March>)> = vec![];
+        // each with a radius their surface orbits pass through
+        let trap = |radius| Some(OrbitTrap::new(Trap::Iterations(radius), ramp()));
+
+        let mut mandelbox = Mandelbox::new(Vec3::new(0.0, 0.0, 0.0), -1.5, 12, Material::sky());
+        mandelbox.coloring = trap(3.0);
+        let mut menger = Menger::new(Vec3::new(0.0, 0.0, 0.0), 2.0, 4, Material::sky());
+        menger.coloring = trap(1.5);
+        fractals.push(("menger", Box::new(menger)));
+        let mut sierpinski = Sierpinski::new(Vec3::new(0.0, 0.0, 0.0), 2.0, 6, Material::sky());
+        sierpinski.coloring = trap(1.5);
+        fractals.push(("sierpinski", Box::new(sierpinski)));
+        let mut kifs = Kifs::new(Vec3::new(0.0, 0.0, 0.0), 3.0, offset, Matrix::identity(), 5, Material::sky());
+        kifs.coloring = trap(1.5);
+        fractals.push(("kifs", Box::new(kifs)));
+        fractals.push(("mandelbox", Box::new(mandelbox)));
+
+        // escaping sooner or later, somewhere on the surface
+        let directions = [
+            Vec3::new(1.0, 0.3, 0.2), Vec3::new(0.4, 1.0, 0.7), Vec3::new(0.2, 0.1, 1.0),
+            Vec3::new(-0.7, 0.5, 0.3), Vec3::new(0.9, 0.8, 0.75), Vec3::new(0.3, -0.6, 0.9),
+            Vec3::new(1.0, 1.0, 1.0), Vec3::new(-0.2, -0.9, -0.4),
+        ];
+        for (name, fractal) in fractals.iter() {
+            let colors: Vec<f64> = directions.iter()
+                .map(|direction| fractal.material_at(surface(&**fractal, *direction)).color.x)
+                .collect();
+            assert!(colors.iter().any(|color| *color != colors[0]), "{} is one color: {:?}", name, colors);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// upright hexagonal prism
+#[derive(Debug, Copy, Clone)]
+pub struct Prism {
+    pub position: Vec3, // center
+    pub radius: f64, // distance from the axis to the middle of each side
+    pub height: f64, // half the distance between the caps
+    pub material: Material,
+}
+
+impl Prism {
+    pub fn new(position: Vec3, radius: f64, height: f64, material: Material) -> Prism {
+        Prism { position, radius, height, material }
+    }
+}
+
+impl March for Prism {
+    fn material(&self) -> Material { self.material }
+
+    // exact hexagonal prism from iquilezles.org/www/articles/distfunctions/distfunctions.htm,
+    // with the axis along y instead of z
+    fn march(&self, point: Vec3) -> f64 {
+        let (kx, ky, kz) = (-0.866_025_403_784_438_6, 0.5, 0.577_350_269_189_625_8);
+        let p = (point - self.position).abs();
+        let (mut x, mut z) = (p.x, p.z);
+
+        // fold into a single sector of the hexagon
+        let fold = 2.0 * (kx * x + ky * z).min(0.0);
+        x -= fold * kx;
+        z -= fold * ky;
+
+        let edge = x.clamp(-kz * self.radius, kz * self.radius);
+        let side = ((x - edge).powi(2) + (z - self.radius).powi(2)).sqrt()
+            * (z - self.radius).signum();
+        let cap = p.y - self.height;
+
+        side.max(cap).min(0.0) + (side.max(0.0).powi(2) + cap.max(0.0).powi(2)).sqrt()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // out to the corners of the hexagon
+        let corner = self.radius * 2.0 / 3.0_f64.sqrt();
+        Some(Aabb::around(self.position, Vec3::new(corner, self.height, corner)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Prism;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let prism = Prism::new(Vec3::new(0.0, 0.0, 0.0), 1.0, 2.0, Material::sky());
+        assert!((prism.march(Vec3::new(0.0, 0.0, 3.0)) - 2.0).abs() < 1e-9);
+        assert!((prism.march(Vec3::new(0.0, 5.0, 0.0)) - 3.0).abs() < 1e-9);
+        assert!((prism.march(Vec3::new(0.0, 0.0, 0.0)) + 1.0).abs() < 1e-9);
+
+        // flat sides every 60 degrees
+        let side = Vec3::new(60.0_f64.to_radians().sin(), 0.0, 60.0_f64.to_radians().cos()) * 3.0;
+        assert!((prism.march(side) - 2.0).abs() < 1e-9);
+    }
+}
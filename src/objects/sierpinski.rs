@@ -0,0 +1,90 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+use crate::objects::orbit::OrbitTrap;
+
+// a tetrahedron made of four half-size tetrahedra, each made of four more, ...
+pub struct Sierpinski {
+    pub position: Vec3, // center
+    pub size: f64, // distance from the center to each axis-aligned face of the bounding cube
+    pub iterations: usize,
+    pub material: Material,
+    pub coloring: Option<OrbitTrap>,
+}
+
+// distance to the tetrahedron with corners at (1, 1, 1), (-1, -1, 1), (1, -1, -1) and (-1, 1, -1)
+fn tetrahedron(p: Vec3) -> f64 {
+    let faces = (-p.x - p.y - p.z).max(p.x + p.y - p.z).max(-p.x + p.y + p.z).max(p.x - p.y + p.z);
+    (faces - 1.0) / 3.0_f64.sqrt()
+}
+
+impl Sierpinski {
+    pub fn new(position: Vec3, size: f64, iterations: usize, material: Material) -> Sierpinski {
+        Sierpinski { position, size, iterations, material, coloring: None }
+    }
+
+    // distance estimate, showing each step of the orbit to visit
+    fn iterate(&self, point: Vec3, mut visit: impl FnMut(Vec3)) -> f64 {
+        let mut z = (point - self.position) / self.size;
+        let mut scale = 1.0;
+
+        for _ in 0..self.iterations {
+            // fold towards the corner at (1, 1, 1)
+            if z.x + z.y < 0.0 { z = Vec3::new(-z.y, -z.x, z.z); }
+            if z.x + z.z < 0.0 { z = Vec3::new(-z.z, z.y, -z.x); }
+            if z.y + z.z < 0.0 { z = Vec3::new(z.x, -z.z, -z.y); }
+
+            // and grow the tetrahedron in that corner to full size
+            z = z * 2.0 - 1.0;
+            scale *= 2.0;
+            visit(z);
+        }
+
+        tetrahedron(z) / scale * self.size
+    }
+}
+
+impl March for Sierpinski {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.iterate(point, |_| ())
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        OrbitTrap::color(&self.coloring, self.material, self.iterations, |visit| { self.iterate(point, visit); })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around(self.position, Vec3::new(self.size, self.size, self.size)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Sierpinski;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let sierpinski = Sierpinski::new(Vec3::new(0.0, 0.0, 0.0), 1.0, 6, Material::sky());
+
+        // every corner is kept
+        for &corner in [
+            Vec3::new(1.0, 1.0, 1.0), Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, 1.0, -1.0),
+        ].iter() {
+            assert!(sierpinski.march(corner * 0.99).abs() < 0.02);
+        }
+
+        // the middle is carved out
+        assert!(sierpinski.march(Vec3::new(0.0, 0.0, 0.0)) > 0.1);
+
+        // far away it's no further than the nearest corner
+        let far = sierpinski.march(Vec3::new(3.0, 3.0, 3.0));
+        assert!(far > 1.0 && far < 2.0 * 3.0_f64.sqrt(), "{}", far);
+    }
+}
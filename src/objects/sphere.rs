@@ -1,8 +1,10 @@
 use crate::structures::vec3::Vec3;
 use crate::structures::ray::Ray;
 use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
 use crate::objects::march::March;
 use crate::objects::trace::Trace;
+use crate::render::EPSILON;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Sphere {
@@ -32,17 +34,31 @@ impl Trace for Sphere {
         let c = oc.dot(&oc) - self.radius * self.radius;
         let disc = (b * b) - (a * c);
 
-        let distance = ((0.0 - b - disc.sqrt()) / a).min((0.0 - b + disc.sqrt()) / a);
+        // the far side is visible from inside the sphere
+        let near = (0.0 - b - disc.sqrt()) / a;
+        let distance = if near > EPSILON { near } else { (0.0 - b + disc.sqrt()) / a };
         let normal = (ray.point_at(&distance) - self.position).unit();
 
         return if disc > 0.0 { Some((distance, normal)) } else { None };
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.position - self.radius, self.position + self.radius))
+    }
 }
 
 impl March for Sphere {
     fn material(&self) -> Material { self.material }
 
     fn march(&self, point: Vec3) -> f64 {
-        (point - self.position).length() - self.radius // TODO modulo with 6 for infinite rep.
+        (point - self.position).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Trace::bounds(self)
+    }
+
+    fn gradient(&self, point: Vec3) -> Option<Vec3> {
+        Some(point - self.position)
     }
 }
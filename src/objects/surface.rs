@@ -0,0 +1,178 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// operators that change the distance to an object's surface,
+// rather than where it's sampled.
+
+// the steepest the noise can get: the quintic fade's slope peaks at 15/8,
+// and neighboring lattice values differ by at most 2, on each of 3 axes
+const NOISE_SLOPE: f64 = 15.0 / 8.0 * 2.0 * 1.732_050_807_568_877_2;
+
+// pseudorandom value in [-1, 1] for a lattice point
+fn hash(x: i64, y: i64, z: i64) -> f64 {
+    let mut h = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+    h ^= h >> 29;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 32;
+    (h >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+// smooth value noise in [-1, 1], varying about once per unit
+pub fn noise(point: Vec3) -> f64 {
+    let (x, y, z) = (point.x.floor(), point.y.floor(), point.z.floor());
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v, w) = (fade(point.x - x), fade(point.y - y), fade(point.z - z));
+    let (x, y, z) = (x as i64, y as i64, z as i64);
+
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let edge = |y, z| lerp(hash(x, y, z), hash(x + 1, y, z), u);
+    let face = |z| lerp(edge(y, z), edge(y + 1, z), v);
+    lerp(face(z), face(z + 1), w)
+}
+
+// grows an object outwards, rounding off its edges
+pub struct Round {
+    pub inner: Box<dyn March>,
+    pub radius: f64,
+}
+
+impl Round {
+    pub fn new(inner: Box<dyn March>, radius: f64) -> Round {
+        Round { inner, radius }
+    }
+}
+
+impl March for Round {
+    fn material(&self) -> Material { self.inner.material() }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(point) }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.inner.bound(point) - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.inner.bounds()?.pad(self.radius))
+    }
+}
+
+// hollows an object out, leaving a shell of thickness on either side of its surface
+pub struct Onion {
+    pub inner: Box<dyn March>,
+    pub thickness: f64,
+}
+
+impl Onion {
+    pub fn new(inner: Box<dyn March>, thickness: f64) -> Onion {
+        Onion { inner, thickness }
+    }
+}
+
+impl March for Onion {
+    fn material(&self) -> Material { self.inner.material() }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(point) }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.inner.bound(point).abs() - self.thickness
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.inner.bounds()?.pad(self.thickness))
+    }
+}
+
+// roughens an object's surface by pushing it in and out with noise.
+// higher frequencies make smaller bumps.
+pub struct Displace {
+    pub inner: Box<dyn March>,
+    pub amplitude: f64,
+    pub frequency: f64,
+}
+
+impl Displace {
+    pub fn new(inner: Box<dyn March>, amplitude: f64, frequency: f64) -> Displace {
+        Displace { inner, amplitude, frequency }
+    }
+}
+
+impl March for Displace {
+    fn material(&self) -> Material { self.inner.material() }
+    fn material_at(&self, point: Vec3) -> Material { self.inner.material_at(point) }
+
+    fn march(&self, point: Vec3) -> f64 {
+        self.inner.bound(point) + self.amplitude * noise(point * self.frequency)
+    }
+
+    // the noise adds its own slope on top of the distance's
+    fn lipschitz(&self) -> f64 {
+        1.0 + self.amplitude.abs() * self.frequency * NOISE_SLOPE
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.inner.bounds()?.pad(self.amplitude.abs()))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use super::{Round, Onion, Displace, noise};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+    use crate::objects::sphere::Sphere;
+
+    fn ball() -> Box<Sphere> {
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, Material::sky()))
+    }
+
+    fn point(rng: &mut StdRng) -> Vec3 {
+        Vec3::new(rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5, rng.gen::<f64>() - 0.5) * 4.0
+    }
+
+    #[test]
+    fn test_round() {
+        let round = Round::new(ball(), 0.5);
+        assert_eq!(round.march(Vec3::new(2.0, 0.0, 0.0)), 0.5);
+    }
+
+    #[test]
+    fn test_onion() {
+        let onion = Onion::new(ball(), 0.1);
+        assert_eq!(onion.march(Vec3::new(0.0, 0.0, 0.0)), 0.9);
+        assert_eq!(onion.march(Vec3::new(0.0, 1.0, 0.0)), -0.1);
+        assert_eq!(onion.march(Vec3::new(0.0, 3.0, 0.0)), 1.9);
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut rng = StdRng::seed_from_u64(34);
+
+        for _ in 0..2000 {
+            let value = noise(point(&mut rng) * 10.0);
+            assert!((-1.0..=1.0).contains(&value));
+        }
+
+        // lattice points land exactly on their hashed value
+        let lattice = Vec3::new(3.0, -2.0, 5.0);
+        assert_eq!(noise(lattice), super::hash(3, -2, 5));
+    }
+
+    #[test]
+    fn test_lipschitz() {
+        let mut rng = StdRng::seed_from_u64(35);
+        let displace = Displace::new(ball(), 0.2, 4.0);
+
+        for _ in 0..2000 {
+            let a = point(&mut rng);
+            let b = a + (point(&mut rng) * 0.02);
+            let change = (displace.bound(a) - displace.bound(b)).abs();
+            assert!(change <= (a - b).length() + 1e-9);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
+use crate::objects::march::March;
+
+// ring lying flat in the xz plane
+#[derive(Debug, Copy, Clone)]
+pub struct Torus {
+    pub position: Vec3,
+    pub major: f64, // radius of the ring
+    pub minor: f64, // radius of the tube
+    pub material: Material,
+}
+
+impl Torus {
+    pub fn new(position: Vec3, major: f64, minor: f64, material: Material) -> Torus {
+        Torus { position, major, minor, material }
+    }
+}
+
+impl March for Torus {
+    fn material(&self) -> Material { self.material }
+
+    fn march(&self, point: Vec3) -> f64 {
+        let p = point - self.position;
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major;
+        (ring * ring + p.y * p.y).sqrt() - self.minor
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let reach = self.major + self.minor;
+        Some(Aabb::around(self.position, Vec3::new(reach, self.minor, reach)))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Torus;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::material::Material;
+    use crate::objects::march::March;
+
+    #[test]
+    fn test_march() {
+        let torus = Torus::new(Vec3::new(0.0, 0.0, 0.0), 2.0, 0.5, Material::sky());
+        assert_eq!(torus.march(Vec3::new(0.0, 0.0, 0.0)), 1.5);
+        assert_eq!(torus.march(Vec3::new(0.0, 0.0, 2.0)), -0.5);
+        assert_eq!(torus.march(Vec3::new(2.0, 1.5, 0.0)), 1.0);
+    }
+}
@@ -1,27 +1,47 @@
+use std::sync::Arc;
+
 use crate::structures::vec3::Vec3;
 use crate::structures::ray::Ray;
 use crate::structures::material::Material;
 use crate::structures::cast::Cast;
+use crate::structures::aabb::Aabb;
 use crate::render::EPSILON;
 
 pub trait Trace: Send + Sync {
     fn material(&self) -> Material;
     fn trace(&self, ray: Ray) -> Option<(f64, Vec3)>; // distance, normal
+
+    // box around the object, None if it goes on forever
+    fn bounds(&self) -> Option<Aabb> { None }
+
+    // the full hit, for objects made of parts with their own materials.
+    // the id is filled in by whatever holds the object.
+    fn cast(&self, ray: Ray) -> Option<Cast> {
+        let (distance, normal) = self.trace(ray)?;
+        Some(Cast { distance, normal, material: self.material(), id: 0 })
+    }
+}
+
+// shared geometry looks just like the geometry
+impl<T: Trace + ?Sized> Trace for Arc<T> {
+    fn material(&self) -> Material { (**self).material() }
+    fn trace(&self, ray: Ray) -> Option<(f64, Vec3)> { (**self).trace(ray) }
+    fn bounds(&self) -> Option<Aabb> { (**self).bounds() }
+    fn cast(&self, ray: Ray) -> Option<Cast> { (**self).cast(ray) }
 }
 
 impl dyn Trace {
     pub fn hit(trace: &Vec<Box<dyn Trace>>, ray: Ray) -> Option<Cast> {
         let mut best: Option<Cast> = None;
 
-        for object in trace.iter() {
-            let (distance, normal) = match object.trace(ray) {
-                Some(v) => v,
+        for (id, object) in trace.iter().enumerate() {
+            let cast = match object.cast(ray) {
+                Some(cast) => Cast { id, ..cast },
                 None => continue,
             };
 
-            let visible = distance > EPSILON;
-            let closer = if let Some(cast) = best { distance < cast.distance  } else { true };
-            let cast = Cast { distance, normal, material: object.material() };
+            let visible = cast.distance > EPSILON;
+            let closer = if let Some(best) = best { cast.distance < best.distance } else { true };
 
             if visible && closer {
                 best = Some(cast);
@@ -0,0 +1,155 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::ray::Ray;
+use crate::structures::material::Material;
+use crate::structures::matrix::Matrix;
+use crate::structures::cast::Cast;
+use crate::structures::aabb::Aabb;
+use crate::objects::trace::Trace;
+use crate::objects::march::March;
+
+// an object moved, rotated and scaled by a matrix.
+// the object is sampled in its own space,
+// and results are mapped back into the world.
+pub struct Transformed<T: ?Sized> {
+    pub inner: Box<T>,
+    matrix: Matrix, // object to world
+    inverse: Matrix, // world to object
+    normal: Matrix, // inverse transpose, keeps normals perpendicular to the surface
+    scale: f64, // the least a unit of object space is shrunk by in the world
+}
+
+impl<T: ?Sized> Transformed<T> {
+    // None if the matrix can't be undone
+    pub fn new(inner: Box<T>, matrix: Matrix) -> Option<Transformed<T>> {
+        let inverse = matrix.inverse()?;
+
+        Some(Transformed {
+            inner,
+            matrix,
+            inverse,
+            normal: inverse.transpose(),
+            scale: 1.0 / inverse.max_stretch(),
+        })
+    }
+
+    // the ray in object space, and how much longer its direction was there
+    fn local(&self, ray: Ray) -> (Ray, f64) {
+        let direction = self.inverse.vector(ray.direction);
+        let stretch = direction.length();
+        (Ray::new(self.inverse.point(ray.origin), direction / stretch), stretch)
+    }
+}
+
+impl<T: Trace + ?Sized> Trace for Transformed<T> {
+    fn material(&self) -> Material { self.inner.material() }
+
+    fn trace(&self, ray: Ray) -> Option<(f64, Vec3)> {
+        let cast = self.cast(ray)?;
+        Some((cast.distance, cast.normal))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.inner.bounds()?.transform(&self.matrix))
+    }
+
+    fn cast(&self, ray: Ray) -> Option<Cast> {
+        let (local, stretch) = self.local(ray);
+        let cast = self.inner.cast(local)?;
+
+        Some(Cast {
+            distance: cast.distance / stretch,
+            normal: self.normal.vector(cast.normal).unit(),
+            ..cast
+        })
+    }
+}
+
+impl<T: March + ?Sized> March for Transformed<T> {
+    fn material(&self) -> Material { self.inner.material() }
+
+    // distances are stretched differently in each direction,
+    // so assume the worst to never step past the surface.
+    // exact for rotations and uniform scales.
+    fn march(&self, point: Vec3) -> f64 {
+        self.inner.march(self.inverse.point(point)) * self.scale
+    }
+
+    fn material_at(&self, point: Vec3) -> Material {
+        self.inner.material_at(self.inverse.point(point))
+    }
+
+    fn lipschitz(&self) -> f64 { self.inner.lipschitz() }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.inner.bounds()?.transform(&self.matrix))
+    }
+
+    fn gradient(&self, point: Vec3) -> Option<Vec3> {
+        Some(self.normal.vector(self.inner.gradient(self.inverse.point(point))?))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::f64::consts::PI;
+
+    use super::Transformed;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::matrix::Matrix;
+    use crate::structures::material::Material;
+    use crate::objects::trace::Trace;
+    use crate::objects::march::March;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::plane::Plane;
+
+    fn unit_sphere() -> Box<Sphere> {
+        Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, Material::sky()))
+    }
+
+    #[test]
+    fn test_trace() {
+        // a sphere of radius 2 at x = 5
+        let matrix = Matrix::translate(Vec3::new(5.0, 0.0, 0.0)) * Matrix::scale(Vec3::new(2.0, 2.0, 2.0));
+        let sphere = Transformed::new(unit_sphere(), matrix).unwrap();
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let (distance, normal) = sphere.trace(ray).unwrap();
+        assert!((distance - 3.0).abs() < 1e-9);
+        assert!((normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_normal() {
+        // a floor tilted 45 degrees around z
+        let floor = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Material::sky());
+        let tilted = Transformed::new(Box::new(floor), Matrix::rotate(Vec3::new(0.0, 0.0, 1.0), PI / 4.0)).unwrap();
+
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let (distance, normal) = tilted.trace(ray).unwrap();
+        assert!((distance - 1.0).abs() < 1e-9);
+        assert!((normal - Vec3::new(-1.0, 1.0, 0.0).unit()).length() < 1e-9);
+
+        // squashing keeps the normal perpendicular to the surface
+        let squashed = Transformed::new(unit_sphere(), Matrix::scale(Vec3::new(1.0, 0.5, 1.0))).unwrap();
+        let ray = Ray::new(Vec3::new(-2.0, 0.25, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let (distance, normal) = squashed.trace(ray).unwrap();
+        let hit = ray.point_at(&distance);
+        let gradient = Vec3::new(hit.x, hit.y * 4.0, hit.z).unit();
+        assert!((normal - gradient).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_march() {
+        let matrix = Matrix::translate(Vec3::new(0.0, 1.0, 0.0)) * Matrix::scale(Vec3::new(3.0, 3.0, 3.0));
+        let sphere = Transformed::new(unit_sphere(), matrix).unwrap();
+        assert!((sphere.march(Vec3::new(0.0, 6.0, 0.0)) - 2.0).abs() < 1e-9);
+
+        // stretched shapes are never overestimated
+        let stretched = Transformed::new(unit_sphere(), Matrix::scale(Vec3::new(4.0, 1.0, 1.0))).unwrap();
+        assert!((stretched.march(Vec3::new(0.0, 3.0, 0.0)) - 2.0).abs() < 1e-9);
+        assert!(stretched.march(Vec3::new(6.0, 0.0, 0.0)) <= 2.0);
+
+        assert!(Transformed::new(unit_sphere(), Matrix::scale(Vec3::new(0.0, 1.0, 1.0))).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::structures::vec3::Vec3;
 use crate::structures::ray::Ray;
 use crate::structures::material::Material;
+use crate::structures::aabb::Aabb;
 use crate::objects::trace::Trace;
 
 use crate::render::EPSILON;
@@ -35,12 +36,42 @@ impl Trace for Triangle {
         let u = d * (0.0 - q).dot(&ac);
         let v = d * q.dot(&ab);
         let t = d * (0.0 - n).dot(&rov0);
-        let hit = u.min(v.min(t.min(1.0 - (u + v)))) > EPSILON;
+        // inside all three edges, and in front of the ray
+        let hit = u.min(v.min(1.0 - (u + v))) >= 0.0 && t > EPSILON;
 
         return if hit {
-            Some(((Vec3::new(t, u, v) - ray.origin).length(), n.unit()))
+            Some((t, n.unit()))
         } else {
             None
         }
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::empty().grow(self.a).grow(self.b).grow(self.c))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Triangle;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::material::Material;
+    use crate::objects::trace::Trace;
+
+    #[test]
+    fn test_trace() {
+        let triangle = Triangle::new(
+            Vec3::new(0.0, 0.0, -2.0),
+            Vec3::new(1.0, 0.0, -2.0),
+            Vec3::new(0.0, 1.0, -2.0),
+            Material::sky(),
+        );
+
+        let ray = Ray::new(Vec3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(triangle.trace(ray).unwrap().0, 3.0);
+
+        let miss = Ray::new(Vec3::new(0.8, 0.8, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(triangle.trace(miss).is_none());
+    }
 }
@@ -3,153 +3,334 @@ use rand::Rng;
 
 use crate::structures::vec3::Vec3;
 use crate::structures::ray::Ray;
-use crate::structures::material::Material;
 use crate::structures::scene::Scene;
 use crate::structures::cast::Cast;
 use crate::structures::camera::Camera;
+use crate::structures::frame::Frame;
+use crate::structures::aov::{Aov, Pixel};
+use crate::structures::stats;
+use crate::bsdf::Bsdf;
 use crate::objects::march::March;
 use crate::objects::trace::Trace;
 
 pub const EPSILON: f64 = 0.0005;
+// scattered rays start this far off the surface
+pub const OFFSET: f64 = EPSILON * 2.0;
 
-fn cast_ray(scene: &Scene, ray: Ray) -> Option<Cast> {
-    let march = March::hit(&scene.march, ray);
+// how sharp preview shadows from marched objects are
+const PREVIEW_HARDNESS: f64 = 16.0;
+// samples, and the spacing between them, for preview ambient occlusion
+const PREVIEW_OCCLUSION: (usize, f64) = (5, 0.05);
+
+pub fn cast_ray(scene: &Scene, ray: Ray) -> Option<Cast> {
+    // marched objects are numbered after traced ones
+    let offset = |cast: Cast| Cast { id: cast.id + scene.trace.len(), ..cast };
+
+    let march = March::hit(&scene.march, ray, &scene.marching);
     let trace = Trace::hit(&scene.trace, ray);
 
-    match (march, trace) {
+    let cast = match (march, trace) {
         (None, None) => None,
         (None, t @ Some(_)) => t,
-        (m @ Some(_), None) => m,
+        (Some(m), None) => Some(offset(m)),
         // trace results are more exact, so favor in a tie.
-        (Some(m), Some(t)) => Some(if m.distance < t.distance { m } else { t }),
-    }
-}
+        (Some(m), Some(t)) => Some(if m.distance < t.distance { offset(m) } else { t }),
+    };
 
-fn sample_sphere() -> Vec3 {
-    let mut rng = rand::thread_rng();
-    let mut point: Vec3 = Vec3::max();
+    stats::record(|stats| {
+        stats.rays += 1;
+        match cast {
+            Some(cast) if cast.id < scene.trace.len() => stats.trace_hits += 1,
+            Some(_) => stats.march_hits += 1,
+            None => (),
+        }
+    });
 
-    // sample point in unit cube, check if in unit sphere
-    while point.length_squared() >= 1.0 {
-        point = Vec3::new(
-            rng.gen::<f64>() * 2.0 - 1.0,
-            rng.gen::<f64>() * 2.0 - 1.0,
-            rng.gen::<f64>() * 2.0 - 1.0,
-         );
-    }
+    cast
+}
 
-    return point;
+// a ray that has bounced depth times on its way from the camera
+fn bounce_ray(scene: &Scene, ray: Ray, depth: usize) -> Option<Cast> {
+    stats::record(|stats| stats.bounce(depth));
+    cast_ray(scene, ray)
 }
 
-fn sample_sphere_surface() -> Vec3 {
-    sample_sphere().unit()
+// how light is gathered for each camera sample
+#[derive(Debug, Copy, Clone)]
+pub enum Integrator {
+    // recursive tree with `branch` rays per bounce,
+    // cost grows as branch^bounces
+    Branched,
+    // a single path carrying its throughput,
+    // ended by russian roulette after min_depth bounces
+    Path { min_depth: usize },
+    // no random bounces: light straight from emissive objects and the sky,
+    // mirror reflections off metals, and a headlight if nothing else lights the scene.
+    // fast, for framing a shot, but not accurate
+    Preview,
 }
 
-fn reflect(v: Vec3, n: Vec3) -> Vec3 {
-    return v - 2.0 * v.dot(&n) * n;
+// a surface point, ready to scatter light
+struct Hit {
+    position: Vec3,
+    normal: Vec3,
+    frame: Frame,
+    wo: Vec3,
+    bsdf: Box<dyn Bsdf>,
+    emission: Vec3,
 }
 
-// adapted from Casual Shadertoy Path Tracing Part III (demofox.org)
-fn fresnel(ior: f64, normal: Vec3, ray: Ray) -> f64 {
-    let mut r0: f64 = (1.0 - ior)/(1.0 + ior);
-    let cosine = -normal.dot(&ray.direction);
-    r0 = r0*r0;
-    let unclamped = r0 + (1.0-r0) * (1.0-cosine).powi(5);
-    return (0.0 as f64).max(unclamped.min(1.0));
+impl Hit {
+    fn new(ray: Ray, cast: Cast) -> Hit {
+        // scatter in a local frame around the outward normal
+        let frame = Frame::new(cast.normal);
+
+        Hit {
+            position: ray.point_at(&cast.distance),
+            normal: cast.normal,
+            frame,
+            wo: frame.to_local(-ray.direction),
+            bsdf: cast.material.bsdf(),
+            emission: cast.material.color * cast.material.emission,
+        }
+    }
+
+    // the scattered ray and its weight, None if absorbed
+    fn scatter(&self, rng: &mut impl Rng) -> Option<(Ray, Vec3)> {
+        let sample = self.bsdf.sample(self.wo, rng.gen(), (rng.gen(), rng.gen()))?;
+
+        // nudge off the side of the surface the ray leaves from
+        let side = if sample.wi.z < 0.0 { -OFFSET } else { OFFSET };
+        let origin = self.position + self.normal * side;
+        Some((Ray::new(origin, self.frame.to_world(sample.wi)), sample.weight))
+    }
+}
+
+// radiance along a ray, split by how many bounces it took to get there
+#[derive(Debug, Copy, Clone)]
+pub struct Light {
+    pub emitted: Vec3, // emitted by the first thing the ray hits
+    pub direct: Vec3, // bounced once
+    pub indirect: Vec3, // bounced more than once
 }
 
-fn refract(v: &Vec3, n: &Vec3, ni_over_nt: f64, refracted: &mut Vec3) -> bool {
-    let uv: Vec3 = v.unit();
-    let dt: f64 = uv.dot(&n);
+impl Light {
+    pub fn black() -> Light {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        Light { emitted: black, direct: black, indirect: black }
+    }
+
+    pub fn emitted(emitted: Vec3) -> Light {
+        Light { emitted, ..Light::black() }
+    }
 
-    let discriminant: f64 = 1.0 - ni_over_nt*ni_over_nt*(1.0 - dt*dt);
-    if discriminant > 0.0 {
-        *refracted = ni_over_nt*(uv - *n*dt) - *n*((discriminant).sqrt());
-        return true;
-    } else {
-        return false;
+    // add light that took this many bounces
+    fn add(&mut self, bounces: usize, value: Vec3) {
+        match bounces {
+            0 => self.emitted  = self.emitted  + value,
+            1 => self.direct   = self.direct   + value,
+            _ => self.indirect = self.indirect + value,
+        }
+    }
+
+    pub fn total(&self) -> Vec3 {
+        self.emitted + self.direct + self.indirect
     }
 }
 
 // simplify
-fn color(scene: &Scene, ray: Ray, bounce: usize, branches: usize) -> Vec3 {
-    let (distance, normal, material) = match cast_ray(&scene, ray) {
-        Some(cast) if bounce != 0 => (cast.distance, cast.normal, cast.material),
+fn color(
+    scene: &Scene,
+    ray: Ray, cast: Option<Cast>,
+    depth: usize, bounce: usize, branches: usize,
+    rng: &mut impl Rng,
+) -> Light {
+    let cast = match cast {
+        Some(cast) if bounce != 0 => cast,
         // hit the sky or traced for too long
-        Some(_) => return Vec3::new(0.0, 0.0, 0.0),
-        _ => return scene.bg.color * scene.bg.emission,
+        Some(_) => return Light::black(),
+        _ => return Light::emitted(scene.bg.color * scene.bg.emission),
     };
 
-    // uncomment to debug depth map:
-    // return Vec3::new(1.0/distance, 1.0/distance, 1.0/distance);
-
-    // uncomment to debug normal map:
-    // return (normal + 1.0) * 0.5;
+    let hit = Hit::new(ray, cast);
+    let mut direct   = Vec3::new(0.0, 0.0, 0.0);
+    let mut indirect = Vec3::new(0.0, 0.0, 0.0);
 
-    let     position     = ray.point_at(&distance);
-    let mut diffuse      = Vec3::new(0.0, 0.0, 0.0);
-    let mut specular     = Vec3::new(0.0, 0.0, 0.0);
-    let     transmission = Vec3::new(0.0, 0.0, 0.0);
-
-    // diffuse
     for _ in 0..branches {
-        let scatter = Ray::new(position, (sample_sphere_surface() + normal).unit());
-        let sample = color(&scene, scatter, bounce - 1, 1); // (samples / 2).max(1)); // only take one sample
+        let (scatter, weight) = match hit.scatter(rng) {
+            Some(s) => s,
+            None => continue, // absorbed
+        };
+
+        let next = bounce_ray(scene, scatter, depth + 1);
+        let incoming = color(scene, scatter, next, depth + 1, bounce - 1, (branches / 2).max(1), rng);
+        direct   = direct   + weight * incoming.emitted;
+        indirect = indirect + weight * (incoming.direct + incoming.indirect);
+    }
 
-        diffuse = diffuse + material.color * sample;
+    Light {
+        emitted:  hit.emission,
+        direct:   direct   / (branches as f64),
+        indirect: indirect / (branches as f64),
     }
+}
 
-    diffuse = diffuse / (branches as f64);
+fn path(
+    scene: &Scene,
+    mut ray: Ray, mut cast: Option<Cast>,
+    bounces: usize, min_depth: usize,
+    rng: &mut impl Rng,
+) -> Light {
+    let mut light      = Light::black();
+    let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+
+    for depth in 0..=bounces {
+        let hit = match cast {
+            Some(cast) if depth != bounces => Hit::new(ray, cast),
+            // traced for too long
+            Some(_) => break,
+            None => {
+                light.add(depth, throughput * scene.bg.color * scene.bg.emission);
+                break;
+            },
+        };
+
+        light.add(depth, throughput * hit.emission);
+
+        let (scatter, weight) = match hit.scatter(rng) {
+            Some(s) => s,
+            None => break, // absorbed
+        };
+
+        throughput = throughput * weight;
+        ray = scatter;
+        cast = bounce_ray(scene, ray, depth + 1);
+
+        // randomly end dim paths, boosting the survivors to stay unbiased
+        if depth + 1 >= min_depth {
+            let survival = throughput.max_element().min(0.95);
+            if rng.gen::<f64>() >= survival { break; }
+            throughput = throughput / survival;
+        }
+    }
 
-    // specular
-    for _ in 0..branches {
-        // let scatter = Ray::new(position, reflect(ray.direction, normal).unit());
-        let scatter = Ray::new(
-            position,
-            reflect(ray.direction, normal + (sample_sphere() * material.roughness)),
-        );
+    light
+}
 
-        let sample = color(&scene, scatter, bounce - 1, (branches / 2).max(1));
-        specular = specular + sample;
-    }
+// an emissive object, seen from afar as a ball of light
+pub struct Lamp {
+    center: Vec3,
+    radius: f64,
+    radiance: Vec3,
+    id: usize, // numbered like casts, traced objects first
+}
 
-    specular = specular / (branches as f64);
+// emissive objects with bounds, anything else can't be aimed at
+pub fn lamps(scene: &Scene) -> Vec<Lamp> {
+    let traced = scene.trace.iter().map(|object| (object.material(), object.bounds()));
+    let marched = scene.march.iter().map(|object| (object.material(), object.bounds()));
+
+    traced.chain(marched).enumerate().filter_map(|(id, (material, bounds))| {
+        let bounds = bounds?;
+        if material.emission <= 0.0 { return None; }
+
+        Some(Lamp {
+            center: bounds.center(),
+            radius: (bounds.max - bounds.min).max_element() * 0.5,
+            radiance: material.color * material.emission,
+            id,
+        })
+    }).collect()
+}
 
-    // this calculation of IOR looks fine,
-    // but specular is defined as a percent,
-    // so this might not be correct
-    let sqrtm = material.specular.sqrt();
-    let ior = (1.0 - sqrtm * 0.28) / (sqrtm * 0.28 + 1.0); // 0.28 is ~ sqrt(0.08)
-    let f = fresnel(ior, normal, ray);
-    // return Vec3::new(f, f, f);
+// how much of a lamp can be seen from a point
+fn visible(scene: &Scene, origin: Vec3, lamp: &Lamp) -> f64 {
+    let to = lamp.center - origin;
+    let distance = to.length();
+    let ray = Ray::new(origin, to / distance);
+    let reach = distance - lamp.radius;
 
-    return pbr(material, transmission, diffuse, specular, f);
+    if let Some(cast) = Trace::hit(&scene.trace, ray) {
+        if cast.id != lamp.id && cast.distance < reach { return 0.0; }
+    }
+
+    stats::record(|stats| stats.shadow_rays += 1);
+    let ignore = lamp.id.checked_sub(scene.trace.len());
+    March::shadow(&scene.march, ray, reach, PREVIEW_HARDNESS, ignore, &scene.marching)
 }
 
-// combine samples in a PBR manner
-pub fn pbr(
-    material: Material,
-    transmission: Vec3,
-    diffuse: Vec3,
-    specular: Vec3,
-    fresnel: f64,
-) -> Vec3 {
-    // TODO: transmission
+fn preview(scene: &Scene, lamps: &[Lamp], ray: Ray, cast: Option<Cast>, depth: usize, bounces: usize) -> Light {
+    let sky = scene.bg.color * scene.bg.emission;
+    let cast = match cast {
+        Some(cast) => cast,
+        None => return Light::emitted(sky),
+    };
+
+    let material = cast.material;
+    let position = ray.point_at(&cast.distance);
+    let origin = position + cast.normal * OFFSET;
+    let black = Vec3::new(0.0, 0.0, 0.0);
+
+    // each lamp is small and far enough away to be treated as a point
+    let mut direct = black;
+    for lamp in lamps.iter().filter(|lamp| lamp.id != cast.id) {
+        let to = lamp.center - position;
+        let distance = to.length();
+        let facing = cast.normal.dot(&(to / distance));
+        if facing <= 0.0 { continue; }
+
+        let size = (lamp.radius / distance).min(1.0).powi(2);
+        direct = direct + lamp.radiance * (facing * size * visible(scene, origin, lamp));
+    }
+
+    let occlusion = March::occlusion(&scene.march, position, cast.normal, PREVIEW_OCCLUSION.1, PREVIEW_OCCLUSION.0);
+    direct = direct + sky * occlusion;
+
+    // light the scene from the camera if nothing else does
+    if lamps.is_empty() && sky.max_element() <= 0.0 {
+        direct = direct + Vec3::new(1.0, 1.0, 1.0) * cast.normal.dot(&-ray.direction).max(0.0);
+    }
+
+    let mirror = material.metallic.clamp(0.0, 1.0);
+    let mut indirect = black;
 
-    // mix transparent and diffuse
-    let base = (transmission * material.transmission) + diffuse * (1.0 - material.transmission);
+    if mirror > 0.0 && bounces > 0 {
+        let reflected = ray.direction - cast.normal * (2.0 * ray.direction.dot(&cast.normal));
+        let bounce = Ray::new(origin, reflected);
+        let next = bounce_ray(scene, bounce, depth + 1);
+        let incoming = preview(scene, lamps, bounce, next, depth + 1, bounces - 1);
+        indirect = material.color * incoming.total() * mirror;
+    }
 
-    // with a specular layer on top
-    let dielectric = (specular * fresnel) + base * (1.0 - fresnel);
-    // for metallic materials
-    let electric = specular * material.color;
+    Light {
+        emitted:  material.color * material.emission,
+        direct:   material.color * direct * (1.0 - mirror),
+        indirect,
+    }
+}
 
-    // lerp electric and dielectric
-    let non_emmisive = (electric * material.metallic) + dielectric * (1.0 - material.metallic);
-    let combined = non_emmisive + material.color * material.emission;
+// every aov for one camera ray
+fn outputs(scene: &Scene, cast: Option<Cast>, light: Light) -> Pixel {
+    let mut pixel = Pixel::empty();
+
+    pixel.set(Aov::Beauty,   light.total());
+    pixel.set(Aov::Emission, light.emitted);
+    pixel.set(Aov::Direct,   light.direct);
+    pixel.set(Aov::Indirect, light.indirect);
+
+    match cast {
+        Some(cast) => {
+            let (object, material) = ((cast.id + 1) as f64, cast.material.id() as f64);
+            pixel.set(Aov::Depth,    Vec3::new(cast.distance, cast.distance, cast.distance));
+            pixel.set(Aov::Normal,   cast.normal);
+            pixel.set(Aov::Albedo,   cast.material.color);
+            pixel.set(Aov::Object,   Vec3::new(object, object, object));
+            pixel.set(Aov::Material, Vec3::new(material, material, material));
+        },
+        None => pixel.set(Aov::Albedo, scene.bg.color),
+    }
 
-    // final color.
-    return combined;
+    pixel
 }
 
 pub fn sample(
@@ -157,17 +338,147 @@ pub fn sample(
     camera: &Camera,
     rng: &mut impl Rng,
     u: f64, v: f64
-) -> Vec3 {
-    let mut aliased = Vec3::new(0.0, 0.0, 0.0);
+) -> Pixel {
+    let mut aliased = Pixel::empty();
+    let mut first   = Pixel::empty();
 
-    for _s in 0..camera.aa {
+    for s in 0..camera.aa {
         // shake pixel around
         let (x, y) = (u + rng.gen::<f64>(), v + rng.gen::<f64>());
         let ray = camera.make_ray(x, y);
 
         // cast ray
-        aliased = aliased + color(&scene, ray, camera.bounces, camera.branch);
+        let cast = bounce_ray(scene, ray, 0);
+        let light = match camera.integrator {
+            Integrator::Branched => color(scene, ray, cast, 0, camera.bounces, camera.branch, rng),
+            Integrator::Path { min_depth } => path(scene, ray, cast, camera.bounces, min_depth, rng),
+            Integrator::Preview => preview(scene, &scene.lamps, ray, cast, 0, camera.bounces),
+        };
+
+        let pixel = outputs(scene, cast, light);
+        if s == 0 { first = pixel; }
+        aliased = aliased + pixel;
+    }
+
+    stats::record(|stats| stats.samples += camera.aa);
+    let mut pixel = aliased / (camera.aa as f64);
+
+    // ids come from a single sample
+    for aov in Aov::ALL.iter().filter(|aov| aov.is_id()) {
+        pixel.set(*aov, first.get(*aov));
+    }
+
+    pixel
+}
+
+#[cfg(test)]
+pub mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::{color, path, preview, lamps, cast_ray};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::scene::Scene;
+    use crate::structures::camera::Camera;
+    use crate::structures::material::Material;
+    use crate::objects::plane::Plane;
+    use crate::objects::sphere::Sphere;
+
+    // a grey floor under a white sky reflects exactly half the sky
+    fn floor() -> Scene {
+        let mut scene = Scene::empty();
+        scene.bg = Material::emissive(Vec3::new(1.0, 1.0, 1.0), 1.0);
+
+        let grey = Material::dielectric(Vec3::new(0.5, 0.5, 0.5), 0.0, 1.0);
+        scene.add_trace(Box::new(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), grey)));
+        scene
+    }
+
+    #[test]
+    fn test_integrators_agree() {
+        let scene = floor();
+        let mut rng = StdRng::seed_from_u64(28);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.2).unit());
+        let samples = 4000;
+
+        let mut branched = Vec3::new(0.0, 0.0, 0.0);
+        let mut traced   = Vec3::new(0.0, 0.0, 0.0);
+
+        for _ in 0..samples {
+            let cast = cast_ray(&scene, ray);
+            branched = branched + color(&scene, ray, cast, 0, 4, 1, &mut rng).total();
+            // roulette from the first bounce on
+            traced = traced + path(&scene, ray, cast, 4, 0, &mut rng).total();
+        }
+
+        let (branched, traced) = (branched / samples as f64, traced / samples as f64);
+        assert!((branched.x - 0.5).abs() < 1e-9);
+        assert!((traced.x - 0.5).abs() < 0.03, "path traced {}", traced.x);
+    }
+
+    // a grey floor under a small lamp, in the dark
+    fn lamp() -> Scene {
+        let mut scene = floor();
+        scene.bg = Material::emissive(Vec3::new(0.0, 0.0, 0.0), 0.0);
+        scene.add_trace(Box::new(Sphere::new(Vec3::new(0.0, 4.0, 0.0), 0.5, Material::emissive(Vec3::new(1.0, 1.0, 1.0), 8.0))));
+        scene
+    }
+
+    fn down(x: f64) -> Ray {
+        Ray::new(Vec3::new(x, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0))
+    }
+
+    #[test]
+    fn test_preview_direct() {
+        let mut scene = lamp();
+        let lit = lamps(&scene);
+        assert_eq!(lit.len(), 1);
+
+        // found once per render, not per sample
+        let camera = Camera::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 60.0, (4, 4), 1, 1, 1);
+        scene.prepare(&camera);
+        assert_eq!(scene.lamps.len(), 1);
+
+        // straight under the lamp, its size is (r / d)^2 of the view
+        let light = preview(&scene, &lit, down(0.0), cast_ray(&scene, down(0.0)), 0, 0);
+        assert!((light.direct.x - 0.5 * 8.0 * (0.5_f64 / 4.0).powi(2)).abs() < 1e-3);
+        assert_eq!(light.emitted.x, 0.0);
+
+        // further out the light comes in at an angle
+        let aside = preview(&scene, &lit, down(2.0), cast_ray(&scene, down(2.0)), 0, 0);
+        assert!(aside.direct.x < light.direct.x && aside.direct.x > 0.0);
+
+        // a ball in the way casts a shadow
+        scene.add_trace(Box::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 0.5, Material::dielectric(Vec3::new(1.0, 1.0, 1.0), 0.0, 1.0))));
+        let lit = lamps(&scene);
+        let ray = Ray::new(Vec3::new(0.8, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let shaded = preview(&scene, &lit, ray, cast_ray(&scene, ray), 0, 0);
+        assert_eq!(shaded.direct.x, 0.0);
+    }
+
+    #[test]
+    fn test_preview_mirror() {
+        // a mirror floor under a white sky shows the sky
+        let mut scene = floor();
+        scene.trace.clear();
+        scene.add_trace(Box::new(Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Material::metal(Vec3::new(0.8, 0.8, 0.8), 0.0))));
+
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.2).unit());
+        let light = preview(&scene, &[], ray, cast_ray(&scene, ray), 0, 1);
+        assert!((light.total().x - 0.8).abs() < 1e-9);
+        assert_eq!(light.direct.x, 0.0);
+
+        // without bounces nothing is reflected
+        assert_eq!(preview(&scene, &[], ray, cast_ray(&scene, ray), 0, 0).total().x, 0.0);
     }
 
-    return aliased / (camera.aa as f64);
+    #[test]
+    fn test_headlight() {
+        // nothing lights the scene, so it's lit from the camera
+        let mut scene = floor();
+        scene.bg = Material::emissive(Vec3::new(0.0, 0.0, 0.0), 0.0);
+        let light = preview(&scene, &[], down(0.0), cast_ray(&scene, down(0.0)), 0, 0);
+        assert!((light.direct.x - 0.5).abs() < 1e-9);
+    }
 }
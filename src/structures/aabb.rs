@@ -0,0 +1,154 @@
+use crate::structures::vec3::Vec3;
+use crate::structures::ray::Ray;
+use crate::structures::matrix::Matrix;
+
+// axis-aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // reaching extent out from center on each axis
+    pub fn around(center: Vec3, extent: Vec3) -> Aabb {
+        Aabb { min: center - extent, max: center + extent }
+    }
+
+    // contains nothing, grows to fit whatever is added to it
+    pub fn empty() -> Aabb {
+        Aabb { min: Vec3::max(), max: -Vec3::max() }
+    }
+
+    pub fn grow(&self, point: Vec3) -> Aabb {
+        Aabb {
+            min: Vec3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            max: Vec3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        self.grow(other.min).grow(other.max)
+    }
+
+    // the part both boxes cover, which is empty if they don't overlap
+    pub fn intersect(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vec3::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z)),
+            max: Vec3::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z)),
+        }
+    }
+
+    // grown by amount on every side
+    pub fn pad(&self, amount: f64) -> Aabb {
+        Aabb { min: self.min - amount, max: self.max + amount }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.x >= self.min.x && point.y >= self.min.y && point.z >= self.min.z
+            && point.x <= self.max.x && point.y <= self.max.y && point.z <= self.max.z
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z), Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z), Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z), Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // box around the transformed box
+    pub fn transform(&self, matrix: &Matrix) -> Aabb {
+        self.corners().iter().fold(Aabb::empty(), |bounds, corner| bounds.grow(matrix.point(*corner)))
+    }
+
+    // distances along the ray to where it enters and leaves the box,
+    // entering at 0 if the ray starts inside
+    pub fn span(&self, ray: Ray) -> Option<(f64, f64)> {
+        let slab = |min: f64, max: f64, origin: f64, direction: f64| {
+            let (a, b) = ((min - origin) / direction, (max - origin) / direction);
+            (a.min(b), a.max(b))
+        };
+
+        if self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z { return None; }
+
+        let (x0, x1) = slab(self.min.x, self.max.x, ray.origin.x, ray.direction.x);
+        let (y0, y1) = slab(self.min.y, self.max.y, ray.origin.y, ray.direction.y);
+        let (z0, z1) = slab(self.min.z, self.max.z, ray.origin.z, ray.direction.z);
+
+        let enter = x0.max(y0).max(z0).max(0.0);
+        let exit = x1.min(y1).min(z1);
+        if enter <= exit { Some((enter, exit)) } else { None }
+    }
+
+    // distance along the ray to where it enters the box,
+    // if it does so before max, 0 if the ray starts inside
+    pub fn hit(&self, ray: Ray, max: f64) -> Option<f64> {
+        let (enter, _) = self.span(ray)?;
+        if enter <= max { Some(enter) } else { None }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::f64::consts::PI;
+
+    use super::Aabb;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::ray::Ray;
+    use crate::structures::matrix::Matrix;
+
+    fn cube() -> Aabb {
+        Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_hit() {
+        let toward = Ray::new(Vec3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(cube().hit(toward, f64::MAX), Some(4.0));
+        assert_eq!(cube().hit(toward, 3.0), None);
+
+        let inside = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(cube().hit(inside, f64::MAX), Some(0.0));
+
+        let away = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(cube().hit(away, f64::MAX), None);
+    }
+
+    #[test]
+    fn test_span() {
+        let through = Ray::new(Vec3::new(-5.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(cube().span(through), Some((4.0, 6.0)));
+        assert_eq!(cube().pad(1.0).span(through), Some((3.0, 7.0)));
+
+        let apart = Aabb::new(Vec3::new(2.0, 2.0, 2.0), Vec3::new(3.0, 3.0, 3.0));
+        assert_eq!(cube().intersect(&apart).span(through), None);
+        assert_eq!(Aabb::empty().span(through), None);
+    }
+
+    #[test]
+    fn test_union() {
+        let other = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 2.0, 1.0));
+        let union = cube().union(&other);
+        assert_eq!(union, Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(3.0, 2.0, 1.0)));
+        assert_eq!(Aabb::empty().union(&other), other);
+    }
+
+    #[test]
+    fn test_transform() {
+        let turned = cube().transform(&Matrix::rotate(Vec3::new(0.0, 1.0, 0.0), PI / 4.0));
+        let diagonal = 2.0_f64.sqrt();
+        assert!((turned.max.x - diagonal).abs() < 1e-9);
+        assert!((turned.max.y - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,96 @@
+use std::ops::{Add, Div};
+
+use crate::structures::vec3::Vec3;
+
+pub const AOVS: usize = 9;
+
+// arbitrary output variables, each rendered as its own layer
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Aov {
+    Beauty, // the final image
+    Depth, // distance to the first hit, 0 for the sky
+    Normal, // world space normal at the first hit
+    Albedo, // surface color at the first hit
+    Emission, // light emitted straight at the camera, including the sky
+    Direct, // light that bounced once on its way to the camera
+    Indirect, // light that bounced more than once
+    Object, // id of the first object hit, 0 for the sky
+    Material, // id of the first material hit, 0 for the sky
+}
+
+impl Aov {
+    pub const ALL: [Aov; AOVS] = [
+        Aov::Beauty,
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Emission,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Object,
+        Aov::Material,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Beauty   => "beauty",
+            Aov::Depth    => "depth",
+            Aov::Normal   => "normal",
+            Aov::Albedo   => "albedo",
+            Aov::Emission => "emission",
+            Aov::Direct   => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Object   => "object",
+            Aov::Material => "material",
+        }
+    }
+
+    // ids are labels, so they can't be averaged across samples
+    pub fn is_id(self) -> bool {
+        self == Aov::Object || self == Aov::Material
+    }
+}
+
+// the value of every aov at one pixel
+#[derive(Debug, Copy, Clone)]
+pub struct Pixel {
+    pub values: [Vec3; AOVS],
+}
+
+impl Pixel {
+    pub fn empty() -> Pixel {
+        Pixel { values: [Vec3::new(0.0, 0.0, 0.0); AOVS] }
+    }
+
+    pub fn get(&self, aov: Aov) -> Vec3 {
+        self.values[aov as usize]
+    }
+
+    pub fn set(&mut self, aov: Aov, value: Vec3) {
+        self.values[aov as usize] = value;
+    }
+}
+
+impl Add<Pixel> for Pixel {
+    type Output = Pixel;
+
+    fn add(self, other: Pixel) -> Pixel {
+        let mut pixel = self;
+        for (value, other) in pixel.values.iter_mut().zip(other.values.iter()) {
+            *value = *value + *other;
+        }
+        pixel
+    }
+}
+
+impl Div<f64> for Pixel {
+    type Output = Pixel;
+
+    fn div(self, other: f64) -> Pixel {
+        let mut pixel = self;
+        for value in pixel.values.iter_mut() {
+            *value = *value / other;
+        }
+        pixel
+    }
+}
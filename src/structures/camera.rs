@@ -1,12 +1,35 @@
-use std::io::Write;
 use std::thread;
-use std::sync::Arc;
+use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use num_cpus;
 
 use crate::structures::vec3::Vec3;
 use crate::structures::ray::Ray;
 use crate::structures::scene::Scene;
-use crate::render::sample;
+use crate::structures::aov::Pixel;
+use crate::structures::film::{Film, Tile};
+use crate::structures::image::Image;
+use crate::structures::control::{Control, Cancel, Progress};
+use crate::structures::stats::{self, Stats};
+use crate::render::{sample, Integrator};
+use crate::denoise::Denoiser;
+
+// tiles are squares this many pixels wide, except at the edges
+pub const TILE: usize = 32;
+
+// which part of the frame is rendered, and what comes out.
+// rays are always made for the whole frame, so a region looks
+// exactly as it would in a full render
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Region {
+    Full,
+    Crop(Tile), // a smaller image of just the region
+    Border(Tile), // a full size image, black outside the region
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct Camera {
@@ -18,6 +41,10 @@ pub struct Camera {
     pub aa:      usize, // samples per pixel
     pub branch:  usize, // branches per bounce (tree-based path tracing)
     pub bounces: usize, // (maximum) number of bounces
+
+    pub integrator: Integrator, // how each sample is traced, branched by default
+    pub denoiser: Denoiser, // cleans up the finished render, off by default
+    pub region: Region, // the whole frame by default
 }
 
 impl Camera {
@@ -31,6 +58,9 @@ impl Camera {
             ray: Ray::new(from, f),
             up, fov, reso,
             aa, branch, bounces,
+            integrator: Integrator::Branched,
+            denoiser: Denoiser::Off,
+            region: Region::Full,
         }
     }
 
@@ -71,74 +101,388 @@ impl Camera {
         )
     }
 
-    pub fn render(self, scene: Scene)  -> Vec<Vec<Vec3>> {
-        // display rendering information
+    // radius of a pixel one unit in front of the camera
+    pub fn footprint(&self) -> f64 {
+        (self.fov.to_radians() / 2.0).tan() * 0.5 / self.height() as f64
+    }
+
+    // print what's about to be rendered
+    pub fn describe(&self, scene: &Scene) {
         println!("Render Information\n");
 
         println!("rendering {} pixel(s):", self.reso.0 * self.reso.1);
         println!(" - {} row(s)", self.reso.1);
         println!(" - {} column(s)\n", self.reso.0);
 
-        println!("taking {} samples(s) per pixel:", self.aa * self.branch.pow(self.bounces as u32));
-        println!(" - {} base sample(s) for AA", self.aa);
-        println!(" - {} bounce(s) per sample", self.bounces);
-        println!(" - {} branch(es) per bounce\n", self.branch);
+        match self.integrator {
+            Integrator::Branched => {
+                println!("taking {} samples(s) per pixel:", self.aa * self.branch.pow(self.bounces as u32));
+                println!(" - {} base sample(s) for AA", self.aa);
+                println!(" - {} bounce(s) per sample", self.bounces);
+                println!(" - {} branch(es) per bounce\n", self.branch);
+            },
+            Integrator::Path { min_depth } => {
+                println!("taking {} path(s) per pixel:", self.aa);
+                println!(" - up to {} bounce(s) per path", self.bounces);
+                println!(" - russian roulette after {} bounce(s)\n", min_depth);
+            },
+            Integrator::Preview => {
+                println!("previewing with {} sample(s) per pixel:", self.aa);
+                println!(" - direct light and ambient occlusion only");
+                println!(" - up to {} mirror bounce(s)\n", self.bounces);
+            },
+        }
 
         println!("scene has {} object(s):", scene.trace.len() + scene.march.len());
         println!(" - {} traced object(s)", scene.trace.len());
         println!(" - {} marched object(s)\n", scene.march.len());
 
-        let camera = Arc::new(self);
-        let scene  = Arc::new(scene);
+        println!("automatically detected {} cpu core(s)", num_cpus::get());
+        if let Denoiser::ATrous { iterations, .. } = self.denoiser {
+            println!("denoising with {} à-trous iteration(s)", iterations);
+        }
+        println!();
+    }
+
+    pub fn render(self, scene: Scene) -> Image<Pixel> {
+        self.describe(&scene);
+        let (image, stats) = self.render_with(scene, &Control::printing())
+            .expect("a render that isn't resumed always starts");
+        stats.print();
+        image
+    }
+
+    // the image is rendered in passes of one sample per pixel,
+    // each pass split into tiles that idle workers pick up in order.
+    // if the render is stopped, the passes so far are averaged.
+    // every tile of every pass has its own seed, so the image doesn't
+    // depend on which worker took what, or on being resumed part way.
+    // fails only if the checkpoint being resumed is for another render
+    pub fn render_with(self, mut scene: Scene, control: &Control) -> io::Result<(Image<Pixel>, Stats)> {
+        scene.prepare(&self);
+
+        let tiles = self.tiles();
+        let jobs = tiles.len() * self.aa;
+        let pixels = self.rendered().area();
+
+        let state = control.start(&self, &scene)?;
+        let skip = state.done.clone();
+
+        let merged = Mutex::new(Stats::default());
+        let next = AtomicUsize::new(0);
+        let taken = AtomicUsize::new(0); // samples promised to tiles, for the budget
+        let done = AtomicUsize::new(skip.iter().filter(|done| **done).count());
+        let sampled = AtomicUsize::new(state.film.count.data().iter().sum());
+        let state = Mutex::new(state);
+        let saved = Mutex::new(Instant::now());
+        let start = Instant::now();
+
+        let progress = |finished| Progress {
+            tiles: done.load(Ordering::Relaxed),
+            total_tiles: jobs,
+            samples: sampled.load(Ordering::Relaxed),
+            total_samples: pixels * self.aa,
+            elapsed: start.elapsed(),
+            finished,
+        };
+
+        thread::scope(|scope| {
+            for _ in 0..num_cpus::get() {
+                scope.spawn(|| {
+                    loop {
+                        let job = next.fetch_add(1, Ordering::Relaxed);
+                        if job >= jobs || control.stopped(start.elapsed()) { break; }
+                        if skip[job] { continue; }
 
-        let num_workers = num_cpus::get();
-        println!("automatically detected {} cpu core(s):", num_workers);
+                        let tile = tiles[job % tiles.len()];
+                        let budget = control.samples.unwrap_or(usize::MAX);
+                        if taken.fetch_add(tile.area(), Ordering::Relaxed) + tile.area() > budget { break; }
 
-        let mut workers = vec![];
-        for worker in 0..num_workers {
-            let start = self.height() * worker / num_workers;
-            let stop   = self.height() * (worker + 1) / num_workers;
+                        let samples = match self.job(&scene, &tiles, job, control.seed, &control.cancel) {
+                            Some(samples) => samples,
+                            None => break,
+                        };
 
-            let camera_clone = Arc::clone(&camera);
-            let scene_clone  = Arc::clone(&scene);
+                        state.lock().expect("a worker panicked").add(job, tile, samples.data());
+                        sampled.fetch_add(tile.area(), Ordering::Relaxed);
+                        done.fetch_add(1, Ordering::Relaxed);
+                        control.report(progress(false));
 
-            workers.push(thread::spawn(move || {
-                Camera::section(camera_clone, scene_clone, start, stop, worker + 1)
-            }));
+                        // only one worker saves at a time, the rest carry on
+                        if let (Some((path, every)), Ok(mut last)) = (&control.checkpoint, saved.try_lock()) {
+                            if last.elapsed() >= *every {
+                                state.lock().expect("a worker panicked").keep(path);
+                                *last = Instant::now();
+                            }
+                        }
+                    }
+
+                    merged.lock().expect("a worker panicked").merge(&stats::take());
+                });
+            }
+        });
+
+        control.report(progress(true));
+        let mut stats = merged.into_inner().expect("a worker panicked");
+        stats.phases.push(("render".to_string(), start.elapsed()));
+
+        if let Some((path, _)) = &control.checkpoint {
+            let saving = Instant::now();
+            state.lock().expect("a worker panicked").keep(path);
+            stats.phases.push(("checkpoint".to_string(), saving.elapsed()));
         }
 
-        let mut result = vec![];
+        let developing = Instant::now();
+        let image = self.develop(&state.into_inner().expect("a worker panicked").film);
+        stats.phases.push(("develop".to_string(), developing.elapsed()));
 
-        for worker in workers {
-            result.append(&mut worker.join().expect("thread failed to return value"))
+        return Ok((image, stats));
+    }
+
+    // the pixels that get rendered, clipped to the frame
+    pub fn rendered(&self) -> Tile {
+        let frame = Tile::frame(self.width(), self.height());
+        match self.region {
+            Region::Full => frame,
+            Region::Crop(region) | Region::Border(region) => region.clip(&frame),
         }
+    }
 
-        println!();
-        return result;
+    // the tiles each pass is split into, in the order they're handed out
+    pub fn tiles(&self) -> Vec<Tile> {
+        self.rendered().split(TILE)
+    }
+
+    // the finished image: the region cut out of the film, denoised on its own
+    // so the unrendered black around it doesn't bleed in, then put back in place if asked
+    pub fn develop(&self, film: &Film) -> Image<Pixel> {
+        let image = film.resolve();
+        if self.region == Region::Full { return self.denoiser.apply(image); }
+
+        let area = self.rendered();
+        let denoised = self.denoiser.apply(image.crop(area));
+
+        match self.region {
+            Region::Border(_) => {
+                let mut framed = Image::new(self.width(), self.height(), Pixel::empty());
+                framed.paste(&denoised, area.x, area.y);
+                framed
+            },
+            _ => denoised,
+        }
     }
 
+    // one pass over one tile, numbered pass by pass.
+    // the result only depends on the job and seed, wherever it's rendered
+    pub fn job(
+        &self, scene: &Scene, tiles: &[Tile],
+        job: usize, seed: u64, cancel: &Cancel,
+    ) -> Option<Image<Pixel>> {
+        let mut rng = StdRng::seed_from_u64(seed ^ (job as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        Camera { aa: 1, ..*self }.section(scene, tiles[job % tiles.len()], &mut rng, cancel)
+    }
+
+    // one sample for every pixel in the tile, the size of the tile,
+    // or nothing if the render is cancelled part way through
     pub fn section(
-        self: Arc<Self>, scene: Arc<Scene>,
-        start: usize, stop: usize, id: usize,
-    ) -> Vec<Vec<Vec3>> {
-        let mut rng = rand::thread_rng();
-        let mut image = vec![];
-
-        for y in start..stop {
-            let mut row = vec![];
-
-            for x in 0..self.width() {
-                row.push(
-                    sample(&scene, &self, &mut rng, x as f64, (self.height() - y) as f64)
-                );
+        &self, scene: &Scene, tile: Tile,
+        rng: &mut impl Rng, cancel: &Cancel,
+    ) -> Option<Image<Pixel>> {
+        let mut samples = Image::new(tile.width, tile.height, Pixel::empty());
+
+        for y in 0..tile.height {
+            if cancel.is_cancelled() { return None; }
+
+            for x in 0..tile.width {
+                let (px, py) = (tile.x + x, tile.y + y);
+                samples.set(x, y, sample(scene, self, rng, px as f64, (self.height() - py) as f64));
             }
+        }
 
-            image.push(row);
-            print!("\r - worker {} is {}% done", id, (y - start + 1) * 100 / (stop - start));
-            std::io::stdout().flush().ok().expect("Could not flush stdout");
+        Some(samples)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::env;
+    use std::time::Duration;
+
+    use super::{Camera, Region, TILE};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::scene::Scene;
+    use crate::structures::control::Control;
+    use crate::structures::material::Material;
+    use crate::structures::aov::{Aov, Pixel};
+    use crate::structures::film::Tile;
+    use crate::structures::image::Image;
+    use crate::objects::sphere::Sphere;
+    use crate::render::Integrator;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+            60.0, (TILE + 8, TILE / 2), 3, 1, 1,
+        )
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut control = Control::new();
+        let reports = control.channel();
+        let (image, _) = camera().render_with(Scene::empty(), &control).unwrap();
+
+        let reports: Vec<_> = reports.try_iter().collect();
+        let last = reports.last().expect("no progress was reported");
+        assert!(last.finished);
+        assert_eq!((last.tiles, last.total_tiles), (6, 6));
+        assert_eq!(last.samples, (TILE + 8) * TILE / 2 * 3);
+        assert_eq!(reports.len(), 7);
+
+        // the empty scene is all sky
+        assert_eq!(image.height(), TILE / 2);
+        assert!(image.data().iter().all(|pixel| pixel.get(Aov::Beauty).x > 0.0));
+    }
+
+    #[test]
+    fn test_budget() {
+        // one pass is enough to fill in the whole image
+        let mut control = Control::new();
+        control.samples = Some((TILE + 8) * TILE / 2);
+        let reports = control.channel();
+        let (image, _) = camera().render_with(Scene::empty(), &control).unwrap();
+
+        let last = reports.try_iter().last().unwrap();
+        assert_eq!((last.tiles, last.samples), (2, (TILE + 8) * TILE / 2));
+        assert!(image.data().iter().all(|pixel| pixel.get(Aov::Beauty).x > 0.0));
+
+        // out of time before starting
+        let mut control = Control::new();
+        control.time = Some(Duration::from_secs(0));
+        let (image, _) = camera().render_with(Scene::empty(), &control).unwrap();
+        assert!(image.data().iter().all(|pixel| pixel.get(Aov::Beauty).x == 0.0));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let control = Control::new();
+        control.cancel.cancel();
+        let (image, _) = camera().render_with(Scene::empty(), &control).unwrap();
+        assert!(image.data().iter().all(|pixel| pixel.get(Aov::Beauty).x == 0.0));
+    }
+
+    #[test]
+    fn test_stats() {
+        // a traced sphere on the left, a marched one on the right
+        let mut scene = Scene::empty();
+        let grey = Material::dielectric(Vec3::new(0.5, 0.5, 0.5), 0.0, 1.0);
+        scene.add_trace(Box::new(Sphere::new(Vec3::new(-1.0, 0.0, -4.0), 1.0, grey)));
+        scene.add_march(Box::new(Sphere::new(Vec3::new(1.0, 0.0, -4.0), 1.0, grey)));
+
+        let mut camera = camera();
+        camera.integrator = Integrator::Path { min_depth: 8 };
+        camera.bounces = 3;
+        let (_, stats) = camera.render_with(scene, &Control::new()).unwrap();
+
+        let samples = (TILE + 8) * TILE / 2 * 3;
+        assert_eq!(stats.samples, samples);
+        assert_eq!(stats.depths[0], samples);
+        assert_eq!(stats.rays, stats.depths.iter().sum::<usize>());
+        assert!(stats.depths.len() <= 4);
+        assert!(stats.trace_hits > 0 && stats.march_hits > 0 && stats.misses() > 0);
+
+        // every ray is marched, unless the traced sphere is in the way
+        assert!(stats.marches <= stats.rays && stats.marches > stats.march_hits);
+        assert!(stats.average_steps() >= 1.0);
+        assert!(stats.phase("render") > Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_resume() {
+        let scene = || {
+            let mut scene = Scene::empty();
+            let grey = Material::dielectric(Vec3::new(0.5, 0.5, 0.5), 0.0, 1.0);
+            scene.add_march(Box::new(Sphere::new(Vec3::new(0.0, 0.0, -3.0), 1.0, grey)));
+            scene
+        };
+        let mut camera = camera();
+        camera.integrator = Integrator::Path { min_depth: 1 };
+
+        let mut whole = Control::new();
+        whole.seed = 42;
+        let (expected, _) = camera.render_with(scene(), &whole).unwrap();
+
+        // stop half way, then pick up from the checkpoint
+        let path = env::temp_dir().join(format!("keikan-resume-{}.checkpoint", std::process::id()));
+        let mut first = Control::new();
+        first.seed = 42;
+        first.samples = Some((TILE + 8) * TILE / 2);
+        first.checkpoint = Some((path.clone(), Duration::from_secs(3600)));
+        let (partial, _) = camera.render_with(scene(), &first).unwrap();
+
+        let mut second = Control::new();
+        second.resume(&path, &camera, &scene()).unwrap();
+        let (resumed, stats) = camera.render_with(scene(), &second).unwrap();
+
+        // a different scene is turned away
+        let mut other = Control::new();
+        assert!(other.resume(&path, &camera, &Scene::empty()).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        // as is a different camera, even once resumed
+        let mut wider = camera;
+        wider.reso = (TILE * 2, TILE / 2);
+        assert!(wider.render_with(scene(), &second).is_err());
+
+        assert_eq!(stats.samples, (TILE + 8) * TILE / 2 * 2);
+        let difference = |a: &Image<Pixel>, b: &Image<Pixel>| a.data().iter().zip(b.data().iter())
+            .map(|(a, b)| (a.get(Aov::Beauty) - b.get(Aov::Beauty)).length())
+            .fold(0.0, f64::max);
+        assert!(difference(&expected, &resumed) < 1e-5);
+        assert!(difference(&expected, &partial) > 1e-3);
+    }
+
+    #[test]
+    fn test_region() {
+        // a sphere off to the side, so its outline crosses the region
+        let scene = || {
+            let mut scene = Scene::empty();
+            let grey = Material::dielectric(Vec3::new(0.5, 0.5, 0.5), 0.0, 1.0);
+            scene.add_march(Box::new(Sphere::new(Vec3::new(0.5, 0.2, -3.0), 1.0, grey)));
+            scene
+        };
+        let mut camera = camera();
+        camera.integrator = Integrator::Preview;
+        let render = |camera: Camera| {
+            let mut control = Control::new();
+            control.seed = 3;
+            camera.render_with(scene(), &control).unwrap().0
+        };
+
+        let region = Tile { x: 20, y: 4, width: 16, height: 12 };
+        let full = render(camera);
+        let cropped = render(Camera { region: Region::Crop(region), ..camera });
+        let bordered = render(Camera { region: Region::Border(region), ..camera });
+
+        assert_eq!((cropped.width(), cropped.height()), (16, 12));
+        assert_eq!((bordered.width(), bordered.height()), (TILE + 8, TILE / 2));
+
+        // the same rays as the full frame, give or take jitter along the outline
+        let id = |pixel: &Pixel| pixel.get(Aov::Object).x;
+        let mut differ = 0;
+        for y in 0..region.height {
+            for x in 0..region.width {
+                let (inside, outside) = (cropped.get(x, y), bordered.get(x + region.x, y + region.y));
+                assert_eq!(inside.get(Aov::Beauty), outside.get(Aov::Beauty));
+                if id(&inside) != id(&full.get(x + region.x, y + region.y)) { differ += 1; }
+            }
         }
+        assert!(cropped.data().iter().any(|pixel| id(pixel) == 1.0));
+        assert!(cropped.data().iter().any(|pixel| id(pixel) == 0.0));
+        assert!(differ < region.height * 2);
 
-        println!();
-        return image;
+        // nothing is rendered outside
+        assert_eq!(bordered.get(0, 0).get(Aov::Beauty), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(bordered.get(region.x + region.width, region.y).get(Aov::Beauty), Vec3::new(0.0, 0.0, 0.0));
     }
 }
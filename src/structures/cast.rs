@@ -8,4 +8,5 @@ pub struct Cast {
     pub distance: f64,
     pub normal: Vec3,
     pub material: Material,
+    pub id: usize, // index of the object in the scene
 }
@@ -0,0 +1,131 @@
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::io;
+
+use crate::structures::camera::Camera;
+use crate::structures::scene::Scene;
+use crate::checkpoint::Checkpoint;
+
+// stops a render early, from any thread holding a clone
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Cancel {
+        Cancel(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// how far along a render is, reported after every tile and once at the end
+#[derive(Debug, Copy, Clone)]
+pub struct Progress {
+    pub tiles: usize, // tiles finished, counting every pass
+    pub total_tiles: usize,
+    pub samples: usize, // camera samples taken
+    pub total_samples: usize,
+    pub elapsed: Duration,
+    pub finished: bool, // no more tiles are coming, whether or not all were rendered
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        self.tiles as f64 / self.total_tiles.max(1) as f64
+    }
+}
+
+// steers a render from outside: when to give up, who to tell how it's going,
+// and where to keep checkpoints
+pub struct Control {
+    pub cancel: Cancel,
+    pub time: Option<Duration>, // stop starting new tiles after this long
+    pub samples: Option<usize>, // total camera samples to take, at most
+    pub progress: Option<Box<dyn Fn(Progress) + Send + Sync>>,
+
+    pub seed: u64, // each tile of each pass draws from its own rng, seeded from this
+    pub checkpoint: Option<(PathBuf, Duration)>, // saved this often, and once more at the end
+    resume: Option<Checkpoint>,
+}
+
+impl Control {
+    pub fn new() -> Control {
+        Control {
+            cancel: Cancel::new(), time: None, samples: None, progress: None,
+            seed: rand::random(), checkpoint: None, resume: None,
+        }
+    }
+
+    // prints a percentage to stdout as the render goes
+    pub fn printing() -> Control {
+        let shown = AtomicUsize::new(usize::MAX);
+
+        let mut control = Control::new();
+        control.progress = Some(Box::new(move |progress: Progress| {
+            let percent = (progress.fraction() * 100.0) as usize;
+            if shown.swap(percent, Ordering::Relaxed) != percent || progress.finished {
+                print!("\r - {}% done after {:.1}s", percent, progress.elapsed.as_secs_f64());
+                std::io::stdout().flush().ok().expect("Could not flush stdout");
+            }
+
+            if progress.finished && progress.tiles < progress.total_tiles {
+                print!(", stopped early at {} of {} samples", progress.samples, progress.total_samples);
+            }
+            if progress.finished { println!("\n"); }
+        }));
+        control
+    }
+
+    // progress as a stream, instead of a callback
+    pub fn channel(&mut self) -> Receiver<Progress> {
+        let (sender, receiver) = channel();
+        self.progress = Some(Box::new(move |progress| { sender.send(progress).ok(); }));
+        receiver
+    }
+
+    // carry on from a checkpoint, as long as it's for this camera and scene
+    pub fn resume(&mut self, path: &Path, camera: &Camera, scene: &Scene) -> io::Result<()> {
+        let checkpoint = Checkpoint::resume(path, camera, scene)?;
+        self.seed = checkpoint.seed;
+        self.resume = Some(checkpoint);
+        Ok(())
+    }
+
+    pub fn resumed(&self) -> Option<&Checkpoint> {
+        self.resume.as_ref()
+    }
+
+    // what a render starts from: the resumed checkpoint, if it's for this
+    // camera and scene, or an empty one
+    pub fn start(&self, camera: &Camera, scene: &Scene) -> io::Result<Checkpoint> {
+        match &self.resume {
+            Some(checkpoint) => checkpoint.check(camera, scene).map(|()| checkpoint.clone()),
+            None => Ok(Checkpoint::new(camera, scene, self.seed, camera.tiles().len() * camera.aa)),
+        }
+    }
+
+    pub fn report(&self, progress: Progress) {
+        if let Some(callback) = &self.progress { callback(progress); }
+    }
+
+    // whether another tile should be started
+    pub fn stopped(&self, elapsed: Duration) -> bool {
+        self.cancel.is_cancelled() || self.time.map_or(false, |time| elapsed >= time)
+    }
+}
+
+impl Default for Control {
+    fn default() -> Control {
+        Control::new()
+    }
+}
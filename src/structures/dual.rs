@@ -0,0 +1,119 @@
+use std::ops::{Add, Mul, Div};
+
+use crate::structures::vec3::Vec3;
+
+// a number along with its gradient, which arithmetic carries along.
+// gives exact derivatives of functions too tangled to work out by hand.
+#[derive(Debug, Copy, Clone)]
+pub struct Dual {
+    pub value: f64,
+    pub gradient: Vec3,
+}
+
+impl Dual {
+    pub fn new(value: f64, gradient: Vec3) -> Dual {
+        Dual { value, gradient }
+    }
+
+    fn chain(self, value: f64, slope: f64) -> Dual {
+        Dual { value, gradient: self.gradient * slope }
+    }
+
+    pub fn sqrt(self) -> Dual {
+        let root = self.value.sqrt();
+        self.chain(root, 0.5 / root)
+    }
+
+    pub fn powf(self, n: f64) -> Dual {
+        self.chain(self.value.powf(n), n * self.value.powf(n - 1.0))
+    }
+
+    pub fn ln(self) -> Dual {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn sin(self) -> Dual { self.chain(self.value.sin(), self.value.cos()) }
+    pub fn cos(self) -> Dual { self.chain(self.value.cos(), -self.value.sin()) }
+
+    // angle of the point (x, self), like f64::atan2
+    pub fn atan2(self, x: Dual) -> Dual {
+        let y = self;
+        let r2 = x.value * x.value + y.value * y.value;
+        Dual {
+            value: y.value.atan2(x.value),
+            gradient: (y.gradient * x.value - x.gradient * y.value) / r2,
+        }
+    }
+}
+
+impl Add<Dual> for Dual {
+    type Output = Dual;
+
+    fn add(self, other: Dual) -> Dual {
+        Dual { value: self.value + other.value, gradient: self.gradient + other.gradient }
+    }
+}
+
+impl Add<f64> for Dual {
+    type Output = Dual;
+
+    fn add(self, other: f64) -> Dual {
+        Dual { value: self.value + other, gradient: self.gradient }
+    }
+}
+
+impl Mul<Dual> for Dual {
+    type Output = Dual;
+
+    fn mul(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value * other.value,
+            gradient: self.gradient * other.value + other.gradient * self.value,
+        }
+    }
+}
+
+impl Mul<f64> for Dual {
+    type Output = Dual;
+
+    fn mul(self, other: f64) -> Dual {
+        Dual { value: self.value * other, gradient: self.gradient * other }
+    }
+}
+
+impl Div<Dual> for Dual {
+    type Output = Dual;
+
+    fn div(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value / other.value,
+            gradient: (self.gradient * other.value - other.gradient * self.value) / (other.value * other.value),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Dual;
+    use crate::structures::vec3::Vec3;
+
+    #[test]
+    fn test_gradient() {
+        // f(x, y) = sin(x * y) + sqrt(x)^3 + atan2(y, x) + ln(x) / (y + 1)
+        let (x, y) = (0.7, 1.3);
+        let dx = Dual::new(x, Vec3::new(1.0, 0.0, 0.0));
+        let dy = Dual::new(y, Vec3::new(0.0, 1.0, 0.0));
+        let f = (dx * dy).sin() + dx.sqrt().powf(3.0) + dy.atan2(dx) + dx.ln() / (dy + 1.0);
+
+        let r2 = x * x + y * y;
+        let expected = Vec3::new(
+            y * (x * y).cos() + 1.5 * x.sqrt() - y / r2 + 1.0 / (x * (y + 1.0)),
+            x * (x * y).cos() + x / r2 - x.ln() / ((y + 1.0) * (y + 1.0)),
+            0.0,
+        );
+
+        let value = (x * y).sin() + x.powf(1.5) + y.atan2(x) + x.ln() / (y + 1.0);
+        assert!((f.value - value).abs() < 1e-12);
+        assert!((f.gradient - expected).length() < 1e-12);
+    }
+}
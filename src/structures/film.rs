@@ -0,0 +1,137 @@
+use crate::structures::aov::{Aov, Pixel};
+use crate::structures::image::Image;
+
+// a rectangle of pixels, rendered as one piece of work
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    pub fn frame(width: usize, height: usize) -> Tile {
+        Tile { x: 0, y: 0, width, height }
+    }
+
+    // cover this tile row by row with smaller ones, clipped at the right and bottom edges
+    pub fn split(&self, size: usize) -> Vec<Tile> {
+        let mut tiles = vec![];
+
+        for y in (self.y..(self.y + self.height)).step_by(size) {
+            for x in (self.x..(self.x + self.width)).step_by(size) {
+                tiles.push(Tile {
+                    x, y,
+                    width: size.min(self.x + self.width - x),
+                    height: size.min(self.y + self.height - y),
+                });
+            }
+        }
+
+        tiles
+    }
+
+    // the part of this tile inside another, maybe empty
+    pub fn clip(&self, other: &Tile) -> Tile {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let right  = (self.x + self.width).min(other.x + other.width).max(x);
+        let bottom = (self.y + self.height).min(other.y + other.height).max(y);
+        Tile { x, y, width: right - x, height: bottom - y }
+    }
+
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+// sums samples as they come in, so the image can be read out at any point
+#[derive(Debug, Clone)]
+pub struct Film {
+    pub sum: Image<Pixel>,
+    pub count: Image<usize>, // samples added to each pixel
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            sum: Image::new(width, height, Pixel::empty()),
+            count: Image::new(width, height, 0),
+        }
+    }
+
+    pub fn width(&self)  -> usize { self.sum.width() }
+    pub fn height(&self) -> usize { self.sum.height() }
+
+    // one sample per pixel in the tile, row by row
+    pub fn add(&mut self, tile: Tile, pixels: &[Pixel]) {
+        for (i, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (tile.x + i % tile.width, tile.y + i / tile.width);
+            let (sum, count) = (self.sum.get(x, y), self.count.get(x, y));
+
+            // ids can't be averaged, so the first sample's are kept
+            let mut added = sum + *pixel;
+            for aov in Aov::ALL.iter().filter(|aov| aov.is_id()) {
+                added.set(*aov, if count == 0 { pixel.get(*aov) } else { sum.get(*aov) });
+            }
+
+            self.sum.set(x, y, added);
+            self.count.set(x, y, count + 1);
+        }
+    }
+
+    // the average so far, black where nothing has been sampled yet
+    pub fn resolve(&self) -> Image<Pixel> {
+        Image::from_fn(self.width(), self.height(), |x, y| {
+            let (sum, count) = (self.sum.get(x, y), self.count.get(x, y));
+            if count == 0 { return Pixel::empty(); }
+
+            let mut pixel = sum / count as f64;
+            for aov in Aov::ALL.iter().filter(|aov| aov.is_id()) {
+                pixel.set(*aov, sum.get(*aov));
+            }
+            pixel
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{Tile, Film};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::aov::{Aov, Pixel};
+
+    #[test]
+    fn test_split() {
+        let tiles = Tile::frame(10, 7).split(4);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles.iter().map(|tile| tile.area()).sum::<usize>(), 70);
+        assert_eq!(tiles[5], Tile { x: 8, y: 4, width: 2, height: 3 });
+
+        let region = Tile { x: 3, y: 2, width: 5, height: 4 };
+        assert_eq!(region.split(4)[1], Tile { x: 7, y: 2, width: 1, height: 4 });
+        assert_eq!(region.clip(&Tile::frame(6, 10)), Tile { x: 3, y: 2, width: 3, height: 4 });
+        assert_eq!(region.clip(&Tile::frame(2, 2)).area(), 0);
+    }
+
+    #[test]
+    fn test_film() {
+        let mut film = Film::new(3, 2);
+        let tile = Tile { x: 1, y: 0, width: 2, height: 2 };
+
+        let sample = |color: f64, id: f64| {
+            let mut pixel = Pixel::empty();
+            pixel.set(Aov::Beauty, Vec3::new(color, color, color));
+            pixel.set(Aov::Object, Vec3::new(id, id, id));
+            pixel
+        };
+
+        film.add(tile, &[sample(1.0, 2.0); 4]);
+        film.add(tile, &[sample(0.0, 5.0); 4]);
+        let image = film.resolve();
+
+        assert_eq!(image.get(0, 0).get(Aov::Beauty), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(image.get(2, 1).get(Aov::Beauty), Vec3::new(0.5, 0.5, 0.5));
+        assert_eq!(image.get(2, 1).get(Aov::Object), Vec3::new(2.0, 2.0, 2.0));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::structures::vec3::Vec3;
+
+// colors blended between stops, from 0 to 1
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    pub stops: Vec<(f64, Vec3)>, // position and color, in order
+}
+
+impl Gradient {
+    pub fn new(stops: Vec<(f64, Vec3)>) -> Gradient {
+        Gradient { stops }
+    }
+
+    // the color at t, held at the ends past the first and last stops
+    pub fn at(&self, t: f64) -> Vec3 {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec3::new(0.0, 0.0, 0.0),
+        };
+
+        if t <= first.0 { return first.1; }
+        if t >= last.0 { return last.1; }
+
+        for pair in self.stops.windows(2) {
+            let ((a, from), (b, to)) = (pair[0], pair[1]);
+            if t <= b {
+                let blend = if b > a { (t - a) / (b - a) } else { 1.0 };
+                return from * (1.0 - blend) + to * blend;
+            }
+        }
+
+        last.1
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Gradient;
+    use crate::structures::vec3::Vec3;
+
+    #[test]
+    fn test_at() {
+        let gradient = Gradient::new(vec![
+            (0.0, Vec3::new(0.0, 0.0, 0.0)),
+            (0.5, Vec3::new(1.0, 0.0, 0.0)),
+            (1.0, Vec3::new(1.0, 1.0, 0.0)),
+        ]);
+
+        assert_eq!(gradient.at(0.25), Vec3::new(0.5, 0.0, 0.0));
+        assert_eq!(gradient.at(0.75), Vec3::new(1.0, 0.5, 0.0));
+        assert_eq!(gradient.at(-1.0), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(gradient.at(2.0), Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(Gradient::new(vec![]).at(0.5), Vec3::new(0.0, 0.0, 0.0));
+    }
+}
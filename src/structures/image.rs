@@ -0,0 +1,154 @@
+use ::image::{ImageBuffer, Rgb, RgbImage};
+
+use crate::structures::vec3::Vec3;
+use crate::structures::aov::{Aov, Pixel};
+use crate::structures::film::Tile;
+
+// a grid of values in one row-major buffer, top row first.
+// holds whole pixels with every aov, or a single layer of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image<T> {
+    width: usize,
+    height: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy> Image<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Image<T> {
+        Image { width, height, data: vec![fill; width * height] }
+    }
+
+    pub fn from_fn(width: usize, height: usize, mut value: impl FnMut(usize, usize) -> T) -> Image<T> {
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                data.push(value(x, y));
+            }
+        }
+        Image { width, height, data }
+    }
+
+    pub fn width(&self)  -> usize { self.width }
+    pub fn height(&self) -> usize { self.height }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> T {
+        self.data[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) {
+        self.data[y * self.width + x] = value;
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.data[(y * self.width)..((y + 1) * self.width)]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(move |y| self.row(y))
+    }
+
+    // every value, row by row
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn map<U: Copy>(&self, convert: impl Fn(T) -> U) -> Image<U> {
+        Image { width: self.width, height: self.height, data: self.data.iter().map(|value| convert(*value)).collect() }
+    }
+
+    // a copy of part of the image, clipped to its edges
+    pub fn crop(&self, area: Tile) -> Image<T> {
+        let area = area.clip(&Tile::frame(self.width, self.height));
+        Image::from_fn(area.width, area.height, |x, y| self.get(area.x + x, area.y + y))
+    }
+
+    // draw another image over this one, with its top left corner at x, y
+    pub fn paste(&mut self, other: &Image<T>, x: usize, y: usize) {
+        let area = Tile { x, y, width: other.width, height: other.height }.clip(&Tile::frame(self.width, self.height));
+
+        for row in 0..area.height {
+            let from = &other.row(row)[..area.width];
+            let start = (y + row) * self.width + x;
+            self.data[start..(start + area.width)].copy_from_slice(from);
+        }
+    }
+}
+
+impl Image<Pixel> {
+    // a single aov of every pixel
+    pub fn layer(&self, aov: Aov) -> Image<Vec3> {
+        self.map(|pixel| pixel.get(aov))
+    }
+}
+
+impl Image<Vec3> {
+    // 8-bit color, for saving with the image crate
+    pub fn to_rgb8(&self, convert: impl Fn(Vec3) -> [u8; 3]) -> RgbImage {
+        let bytes = self.data.iter().flat_map(|value| convert(*value)).collect();
+        ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(self.width as u32, self.height as u32, bytes)
+            .expect("three bytes per pixel always fill the buffer")
+    }
+
+    // 8-bit color as it's stored, from 0 to 1
+    pub fn from_rgb8(buffer: &RgbImage) -> Image<Vec3> {
+        let (width, height) = buffer.dimensions();
+        Image::from_fn(width as usize, height as usize, |x, y| {
+            let [r, g, b] = buffer.get_pixel(x as u32, y as u32).0;
+            Vec3::new(r as f64, g as f64, b as f64) / 255.0
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::Image;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::aov::{Aov, Pixel};
+    use crate::structures::film::Tile;
+
+    #[test]
+    fn test_image() {
+        let mut image = Image::from_fn(4, 3, |x, y| x + 10 * y);
+        assert_eq!(image.get(3, 2), 23);
+        assert_eq!(image.row(1), &[10, 11, 12, 13]);
+
+        image.set(0, 1, 99);
+        assert_eq!(image.rows().nth(1).unwrap()[0], 99);
+
+        let crop = image.crop(Tile { x: 2, y: 1, width: 5, height: 1 });
+        assert_eq!((crop.width(), crop.height(), crop.data()), (2, 1, &[12, 13][..]));
+
+        // pasting off the edge is clipped
+        let mut canvas = Image::new(3, 3, 0);
+        canvas.paste(&Image::new(2, 2, 1), 2, 1);
+        assert_eq!(canvas.data(), &[0, 0, 0, 0, 0, 1, 0, 0, 1]);
+
+        assert!(Image::new(0, 5, 0).is_empty());
+        assert_eq!(Image::new(0, 5, 0).rows().count(), 5);
+    }
+
+    #[test]
+    fn test_layers() {
+        let image = Image::from_fn(2, 2, |x, y| {
+            let mut pixel = Pixel::empty();
+            pixel.set(Aov::Depth, Vec3::new(x as f64, y as f64, 0.0));
+            pixel
+        });
+
+        let depth = image.layer(Aov::Depth);
+        assert_eq!(depth.get(1, 0), Vec3::new(1.0, 0.0, 0.0));
+
+        let rgb = depth.to_rgb8(|value| [(value.x * 255.0) as u8, (value.y * 255.0) as u8, 7]);
+        assert_eq!(rgb.dimensions(), (2, 2));
+        assert_eq!(rgb.get_pixel(0, 1).0, [0, 255, 7]);
+        assert_eq!(Image::from_rgb8(&rgb).get(1, 1), Vec3::new(1.0, 1.0, 7.0 / 255.0));
+    }
+}
@@ -1,4 +1,13 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use crate::structures::vec3::Vec3;
+use crate::bsdf::Bsdf;
+use crate::bsdf::principled::{Principled, DIELECTRIC_F0};
+use crate::bsdf::diffuse::Diffuse;
+use crate::bsdf::conductor::Conductor;
+use crate::bsdf::dielectric::Dielectric;
+use crate::bsdf::mix::Mix;
 
 // TODO: derive debug.. etc. for other structs
 #[derive(Debug, Copy, Clone)]
@@ -64,5 +73,57 @@ impl Material {
     }
 
     // TODO: transparent
-    // convert ior to specular using polynomial approx
+
+    // blend between two materials, 0 is all a, 1 is all b
+    pub fn lerp(a: Material, b: Material, t: f64) -> Material {
+        let mix = |x: f64, y: f64| x * (1.0 - t) + y * t;
+
+        Material {
+            color: a.color * (1.0 - t) + b.color * t,
+            emission: mix(a.emission, b.emission),
+
+            metallic: mix(a.metallic, b.metallic),
+            specular: mix(a.specular, b.specular),
+            roughness: mix(a.roughness, b.roughness),
+
+            transmission: mix(a.transmission, b.transmission),
+        }
+    }
+
+    // the same for materials with the same parameters
+    pub fn id(&self) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        let values = [
+            self.color.x, self.color.y, self.color.z, self.emission,
+            self.metallic, self.specular, self.roughness, self.transmission,
+        ];
+
+        for value in values.iter() {
+            value.to_bits().hash(&mut hasher);
+        }
+
+        hasher.finish() as u32
+    }
+
+    // index of refraction with the reflectance given by specular
+    pub fn ior(&self) -> f64 {
+        let r = (DIELECTRIC_F0 * self.specular).sqrt();
+        (1.0 + r) / (1.0 - r)
+    }
+
+    // how the surface scatters light, emission is handled separately
+    pub fn bsdf(&self) -> Box<dyn Bsdf> {
+        let opaque: Box<dyn Bsdf> = if self.metallic >= 1.0 {
+            Box::new(Conductor::new(self.color, self.roughness))
+        } else if self.metallic <= 0.0 && self.specular <= 0.0 {
+            Box::new(Diffuse::new(self.color))
+        } else {
+            Box::new(Principled::new(self))
+        };
+
+        if self.transmission <= 0.0 { return opaque; }
+
+        let glass = Box::new(Dielectric::new(self.color, self.ior(), self.roughness));
+        Box::new(Mix::new(opaque, glass, self.transmission))
+    }
 }
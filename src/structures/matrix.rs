@@ -0,0 +1,182 @@
+use std::ops::Mul;
+
+use crate::structures::vec3::Vec3;
+
+// 4x4 matrix for affine transforms, in row-major order.
+// the bottom row is always 0 0 0 1,
+// so points are moved by the last column and vectors are not.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Matrix {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix {
+    pub fn identity() -> Matrix {
+        Matrix::linear(
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        )
+    }
+
+    // from the rows of the upper 3x3, with no translation
+    fn linear(x: Vec3, y: Vec3, z: Vec3) -> Matrix {
+        Matrix { m: [
+            [x.x, x.y, x.z, 0.0],
+            [y.x, y.y, y.z, 0.0],
+            [z.x, z.y, z.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ] }
+    }
+
+    pub fn translate(offset: Vec3) -> Matrix {
+        let mut matrix = Matrix::identity();
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+
+    pub fn scale(factor: Vec3) -> Matrix {
+        Matrix::linear(
+            Vec3::new(factor.x, 0.0, 0.0),
+            Vec3::new(0.0, factor.y, 0.0),
+            Vec3::new(0.0, 0.0, factor.z),
+        )
+    }
+
+    // counterclockwise by angle radians, looking down the axis
+    pub fn rotate(axis: Vec3, angle: f64) -> Matrix {
+        let Vec3 { x, y, z } = axis.unit();
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+
+        Matrix::linear(
+            Vec3::new(c + x * x * t,     x * y * t - z * s, x * z * t + y * s),
+            Vec3::new(y * x * t + z * s, c + y * y * t,     y * z * t - x * s),
+            Vec3::new(z * x * t - y * s, z * y * t + x * s, c + z * z * t    ),
+        )
+    }
+
+    fn row(&self, i: usize) -> Vec3 {
+        Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2])
+    }
+
+    fn translation(&self) -> Vec3 {
+        Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    pub fn point(&self, point: Vec3) -> Vec3 {
+        self.vector(point) + self.translation()
+    }
+
+    pub fn vector(&self, vector: Vec3) -> Vec3 {
+        Vec3::new(self.row(0).dot(&vector), self.row(1).dot(&vector), self.row(2).dot(&vector))
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut transposed = *self;
+        for (i, row) in transposed.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        transposed
+    }
+
+    // undoes an affine transform, None if it flattens space
+    pub fn inverse(&self) -> Option<Matrix> {
+        let (x, y, z) = (self.row(0), self.row(1), self.row(2));
+        let det = x.dot(&y.cross(&z));
+        if det == 0.0 { return None; }
+
+        // the columns of the inverse of the upper 3x3
+        let (a, b, c) = (y.cross(&z) / det, z.cross(&x) / det, x.cross(&y) / det);
+        let linear = Matrix::linear(
+            Vec3::new(a.x, b.x, c.x),
+            Vec3::new(a.y, b.y, c.y),
+            Vec3::new(a.z, b.z, c.z),
+        );
+
+        Some(Matrix::translate(-linear.vector(self.translation())) * linear)
+    }
+
+    // the most any vector is lengthened by the transform,
+    // found by power iteration on the transpose times the matrix
+    pub fn max_stretch(&self) -> f64 {
+        let square = self.transpose() * *self;
+        let mut vector = Vec3::new(1.0, 0.9, 0.8).unit();
+
+        for _ in 0..64 {
+            let next = square.vector(vector);
+            if next.length() == 0.0 { return 0.0; }
+            vector = next.unit();
+        }
+
+        self.vector(vector).length()
+    }
+}
+
+impl Mul<Matrix> for Matrix {
+    type Output = Matrix;
+
+    // applies other first, then self
+    fn mul(self, other: Matrix) -> Matrix {
+        let mut product = Matrix { m: [[0.0; 4]; 4] };
+
+        for (i, row) in product.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+
+        product
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::f64::consts::PI;
+
+    use super::Matrix;
+    use crate::structures::vec3::Vec3;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_point_and_vector() {
+        let matrix = Matrix::translate(Vec3::new(1.0, 2.0, 3.0)) * Matrix::scale(Vec3::new(2.0, 2.0, 2.0));
+        assert_eq!(matrix.point(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(3.0, 2.0, 3.0));
+        assert_eq!(matrix.vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let quarter = Matrix::rotate(Vec3::new(0.0, 1.0, 0.0), PI / 2.0);
+        assert!(close(quarter.vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 0.0, -1.0)));
+        assert!(close(quarter.vector(Vec3::new(0.0, 1.0, 0.0)), Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let matrix = Matrix::translate(Vec3::new(1.0, -2.0, 3.0))
+            * Matrix::rotate(Vec3::new(1.0, 1.0, 0.0), 0.7)
+            * Matrix::scale(Vec3::new(1.0, 2.0, 0.5));
+        let point = Vec3::new(0.3, -0.4, 5.0);
+
+        let inverse = matrix.inverse().unwrap();
+        assert!(close(inverse.point(matrix.point(point)), point));
+        assert!(close((matrix * inverse).point(point), point));
+
+        assert_eq!(Matrix::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn test_max_stretch() {
+        let matrix = Matrix::rotate(Vec3::new(0.2, 1.0, 0.5), 1.3) * Matrix::scale(Vec3::new(1.0, 3.0, 0.5));
+        assert!((matrix.max_stretch() - 3.0).abs() < 1e-9);
+        assert!((matrix.inverse().unwrap().max_stretch() - 2.0).abs() < 1e-9);
+    }
+}
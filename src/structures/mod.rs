@@ -4,3 +4,13 @@ pub mod material;
 pub mod camera;
 pub mod scene;
 pub mod cast;
+pub mod frame;
+pub mod aov;
+pub mod matrix;
+pub mod aabb;
+pub mod gradient;
+pub mod dual;
+pub mod film;
+pub mod image;
+pub mod control;
+pub mod stats;
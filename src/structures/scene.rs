@@ -1,16 +1,26 @@
 use crate::structures::material::Material;
-use crate::objects::march::March;
+use crate::structures::camera::Camera;
+use crate::objects::march::{March, Marching};
 use crate::objects::trace::Trace;
+use crate::render::{Lamp, lamps};
 
 pub struct Scene {
     pub march: Vec<Box<dyn March>>,
     pub trace: Vec<Box<dyn Trace>>,
     pub bg: Material,
+    pub marching: Marching, // how marched objects are stepped through
+    pub lamps: Vec<Lamp>, // lights the preview aims at, found by prepare
 }
 
 impl Scene {
     pub fn empty() -> Scene {
-        Scene { march: vec![], trace: vec![], bg: Material::sky() }
+        Scene { march: vec![], trace: vec![], bg: Material::sky(), marching: Marching::new(), lamps: vec![] }
+    }
+
+    // work out what every sample would otherwise redo, once the scene is built
+    pub fn prepare(&mut self, camera: &Camera) {
+        self.marching.footprint = camera.footprint();
+        self.lamps = lamps(self);
     }
 
     pub fn add_march(&mut self, march: Box<dyn March>) {
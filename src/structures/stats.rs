@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+// what a render actually did, counted by each worker on its own and merged at the end
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub samples: usize, // camera samples
+    pub rays: usize, // rays cast into the scene, from the camera or bounced
+    pub trace_hits: usize, // rays whose nearest hit was traced
+    pub march_hits: usize, // or marched
+    pub shadow_rays: usize,
+    pub marches: usize, // rays marched, hit or not
+    pub march_steps: usize,
+    pub exhausted: usize, // marches that ran out of steps before hitting or missing
+    pub depths: Vec<usize>, // rays cast after each number of bounces, camera rays first
+    pub phases: Vec<(String, Duration)>, // wall time, in the order things happened
+}
+
+thread_local! {
+    static LOCAL: RefCell<Stats> = RefCell::new(Stats::default());
+}
+
+// count something on this thread
+pub fn record(update: impl FnOnce(&mut Stats)) {
+    LOCAL.with(|local| update(&mut local.borrow_mut()));
+}
+
+// everything counted on this thread so far, starting over
+pub fn take() -> Stats {
+    LOCAL.with(|local| std::mem::take(&mut *local.borrow_mut()))
+}
+
+impl Stats {
+    pub fn bounce(&mut self, depth: usize) {
+        if self.depths.len() <= depth { self.depths.resize(depth + 1, 0); }
+        self.depths[depth] += 1;
+    }
+
+    pub fn march(&mut self, steps: usize, exhausted: bool) {
+        self.marches += 1;
+        self.march_steps += steps;
+        if exhausted { self.exhausted += 1; }
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        self.samples     += other.samples;
+        self.rays        += other.rays;
+        self.trace_hits  += other.trace_hits;
+        self.march_hits  += other.march_hits;
+        self.shadow_rays += other.shadow_rays;
+        self.marches     += other.marches;
+        self.march_steps += other.march_steps;
+        self.exhausted   += other.exhausted;
+
+        if self.depths.len() < other.depths.len() { self.depths.resize(other.depths.len(), 0); }
+        for (count, other) in self.depths.iter_mut().zip(other.depths.iter()) {
+            *count += other;
+        }
+        self.phases.extend(other.phases.iter().cloned());
+    }
+
+    pub fn phase(&self, name: &str) -> Duration {
+        self.phases.iter().filter(|(phase, _)| phase == name).map(|(_, time)| *time).sum()
+    }
+
+    // rays that hit neither traced nor marched objects
+    pub fn misses(&self) -> usize {
+        self.rays.saturating_sub(self.trace_hits + self.march_hits)
+    }
+
+    pub fn average_steps(&self) -> f64 {
+        self.march_steps as f64 / self.marches.max(1) as f64
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.phase("render").as_secs_f64();
+        if seconds > 0.0 { self.rays as f64 / seconds } else { 0.0 }
+    }
+
+    pub fn print(&self) {
+        println!("Render Statistics\n");
+
+        println!("cast {} ray(s) for {} sample(s):", self.rays, self.samples);
+        println!(" - {} traced hit(s)", self.trace_hits);
+        println!(" - {} marched hit(s)", self.march_hits);
+        println!(" - {} miss(es)", self.misses());
+        println!(" - {} shadow ray(s)", self.shadow_rays);
+        println!(" - {:.0} ray(s) per second\n", self.rays_per_second());
+
+        println!("marched {} ray(s):", self.marches);
+        println!(" - {:.1} step(s) on average", self.average_steps());
+        println!(" - {} ran out of steps\n", self.exhausted);
+
+        println!("rays by bounce:");
+        for (depth, count) in self.depths.iter().enumerate() {
+            println!(" - {}: {}", depth, count);
+        }
+        println!();
+
+        println!("time taken:");
+        for (name, time) in self.phases.iter() {
+            println!(" - {}: {:.2}s", name, time.as_secs_f64());
+        }
+        println!();
+    }
+
+    // the same numbers, for other tools to read
+    pub fn json(&self) -> String {
+        let depths: Vec<String> = self.depths.iter().map(|count| count.to_string()).collect();
+        let phases: Vec<String> = self.phases.iter()
+            .map(|(name, time)| format!("\"{}\": {}", name, time.as_secs_f64()))
+            .collect();
+
+        format!(
+            concat!(
+                "{{\n",
+                "  \"samples\": {},\n",
+                "  \"rays\": {},\n",
+                "  \"trace_hits\": {},\n",
+                "  \"march_hits\": {},\n",
+                "  \"misses\": {},\n",
+                "  \"shadow_rays\": {},\n",
+                "  \"marches\": {},\n",
+                "  \"march_steps\": {},\n",
+                "  \"average_march_steps\": {},\n",
+                "  \"exhausted_marches\": {},\n",
+                "  \"rays_per_second\": {},\n",
+                "  \"depths\": [{}],\n",
+                "  \"phases\": {{{}}}\n",
+                "}}\n",
+            ),
+            self.samples, self.rays, self.trace_hits, self.march_hits, self.misses(),
+            self.shadow_rays, self.marches, self.march_steps, self.average_steps(),
+            self.exhausted, self.rays_per_second(), depths.join(", "), phases.join(", "),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Stats, record, take};
+
+    #[test]
+    fn test_merge() {
+        // each thread counts on its own
+        let worker = thread::spawn(|| {
+            record(|stats| { stats.rays += 3; stats.bounce(2); stats.march(10, false); stats.march_hits += 1; });
+            take()
+        }).join().unwrap();
+        assert_eq!(take(), Stats::default());
+
+        let mut merged = Stats { rays: 1, depths: vec![1], ..Stats::default() };
+        merged.merge(&worker);
+        merged.merge(&worker);
+
+        assert_eq!(merged.rays, 7);
+        assert_eq!(merged.depths, vec![1, 0, 2]);
+        assert_eq!((merged.marches, merged.march_hits, merged.average_steps()), (2, 2, 10.0));
+        assert_eq!(merged.misses(), 5);
+    }
+
+    #[test]
+    fn test_json() {
+        let mut stats = Stats { rays: 10, depths: vec![4, 6], ..Stats::default() };
+        stats.phases.push(("render".to_string(), Duration::from_millis(500)));
+
+        let json = stats.json();
+        assert!(json.contains("\"rays\": 10,"));
+        assert!(json.contains("\"depths\": [4, 6],"));
+        assert!(json.contains("\"rays_per_second\": 20,"));
+        assert!(json.contains("\"phases\": {\"render\": 0.5}"));
+    }
+}
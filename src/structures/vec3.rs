@@ -1,4 +1,4 @@
-use std::ops::{Add, Sub, Mul, Div};
+use std::ops::{Add, Sub, Mul, Div, Neg};
 use std::f64;
 
 #[derive(Debug, Copy, Clone)]
@@ -60,6 +60,10 @@ impl Vec3 {
         self.x + self.y + self.z
     }
 
+    pub fn max_element(&self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
+
     pub fn abs(&self) -> Vec3 {
         Vec3 {
             x: self.x.abs(),
@@ -192,6 +196,18 @@ impl Sub<Vec3> for f64 {
     }
 }
 
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
 // - ...
 // - Vec3 piecewise multiplication for Vec3s and scalars
 // - ...
@@ -350,6 +366,14 @@ pub mod test {
         );
     }
 
+    #[test]
+    fn test_neg() {
+        let vec = Vec3::new(1.0, -2.0, 0.3);
+        let test = Vec3::new(-1.0, 2.0, -0.3);
+
+        assert_eq!(-vec, test);
+    }
+
     #[test]
     fn test_mul_vec() {
         let vec = Vec3::new(1.0, -2.0, 0.3);
@@ -434,6 +458,12 @@ pub mod test {
         assert_eq!(vec.length_squared(), 50.0);
     }
 
+    #[test]
+    fn test_max_element() {
+        let vec = Vec3::new(0.2, -3.0, 0.7);
+        assert_eq!(vec.max_element(), 0.7);
+    }
+
     #[test]
     fn test_unit() {
         let vec = Vec3::new(0.0, -3.0, 4.0);
@@ -0,0 +1,287 @@
+use std::thread;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+
+use crate::structures::vec3::Vec3;
+use crate::structures::ray::Ray;
+use crate::structures::matrix::Matrix;
+use crate::structures::scene::Scene;
+use crate::structures::camera::Camera;
+use crate::structures::aov::Aov;
+use crate::structures::image::Image;
+use crate::render::{sample, cast_ray, Integrator};
+
+// radians turned per pixel the mouse is dragged
+const DRAG_SPEED: f64 = 0.01;
+// radians turned, or fraction of the distance moved, per frame a key is held
+const KEY_SPEED: f64 = 0.03;
+// fraction of the distance zoomed per notch of the scroll wheel
+const ZOOM_SPEED: f64 = 0.1;
+// right after the camera moves, only one pixel in each block this wide is sampled
+const BLOCK: usize = 8;
+
+// the camera as it circles around the point it's looking at
+#[derive(Debug, Copy, Clone)]
+struct Orbit {
+    target: Vec3,
+    offset: Vec3, // from the target to the camera
+    up: Vec3,
+}
+
+impl Orbit {
+    // look at whatever is in the middle of the view
+    fn new(camera: &Camera, scene: &Scene) -> Orbit {
+        let distance = cast_ray(scene, camera.ray).map_or(5.0, |cast| cast.distance);
+
+        Orbit {
+            target: camera.ray.point_at(&distance),
+            offset: -camera.ray.direction * distance,
+            up: camera.up.unit(),
+        }
+    }
+
+    fn right(&self) -> Vec3 {
+        (-self.offset).cross(&self.up).unit()
+    }
+
+    // around the up axis, then over the target, stopping short of the poles
+    fn spin(&mut self, yaw: f64, pitch: f64) {
+        let turned = Matrix::rotate(self.up, yaw).vector(self.offset);
+        let tilted = Matrix::rotate(self.right(), pitch).vector(turned);
+        let pole = tilted.unit().dot(&self.up).abs();
+        self.offset = if pole < 0.99 { tilted } else { turned };
+    }
+
+    // slide the target across the view, by fractions of the distance to it
+    fn pan(&mut self, across: f64, along: f64) {
+        let right = self.right();
+        let above = right.cross(&-self.offset).unit();
+        let distance = self.offset.length();
+        self.target = self.target + (right * across + above * along) * distance;
+    }
+
+    fn zoom(&mut self, factor: f64) {
+        self.offset = self.offset * factor;
+    }
+
+    fn apply(&self, camera: &mut Camera) {
+        camera.ray = Ray::new(self.target + self.offset, (-self.offset).unit());
+    }
+}
+
+// the camera as it would be written in a demo
+fn print(camera: &Camera, orbit: &Orbit) {
+    let v = |v: Vec3| format!("Vec3::new({:.3}, {:.3}, {:.3})", v.x, v.y, v.z);
+
+    println!("Camera::new(");
+    println!("    {},", v(camera.ray.origin));
+    println!("    {},", v(orbit.target));
+    println!("    {},", v(camera.up));
+    println!("    {:.1},", camera.fov);
+    println!("    RESOLUTION,");
+    println!("    {}, {}, {},", camera.aa, camera.branch, camera.bounces);
+    println!(");\n");
+}
+
+// one sample for one pixel in every block, filling the block,
+// with rows split between the cores
+fn pass(scene: &Scene, camera: &Camera, block: usize) -> Image<Vec3> {
+    let (width, height) = (camera.width(), camera.height());
+    let mut image = Image::new(width, height, Vec3::new(0.0, 0.0, 0.0));
+
+    let mut bands: Vec<(usize, &mut [Vec3])> = image.data_mut().chunks_mut(width * block).enumerate().collect();
+    let per_worker = (bands.len() + num_cpus::get() - 1) / num_cpus::get();
+
+    thread::scope(|scope| {
+        for group in bands.chunks_mut(per_worker.max(1)) {
+            scope.spawn(move || {
+                let mut rng = rand::thread_rng();
+
+                for (index, band) in group.iter_mut() {
+                    let y = *index * block;
+
+                    for x in (0..width).step_by(block) {
+                        let pixel = sample(scene, camera, &mut rng, x as f64, (height - y) as f64);
+                        let color = pixel.get(Aov::Beauty);
+
+                        for row in band.chunks_mut(width) {
+                            for value in row[x..(x + block).min(width)].iter_mut() {
+                                *value = color;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    image
+}
+
+fn pack(color: Vec3) -> u32 {
+    let [r, g, b] = color.colorize(1.0);
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+// a window that keeps refining the render of the scene,
+// starting over whenever the camera is moved
+pub fn open(mut scene: Scene, camera: Camera) {
+    let (width, height) = (camera.width(), camera.height());
+    let mut window = match Window::new("keikan", width, height, WindowOptions::default()) {
+        Ok(window) => window,
+        Err(error) => {
+            eprintln!("Could not open window: {}", error);
+            return;
+        },
+    };
+    window.set_target_fps(60);
+
+    println!("drag to orbit, right drag to pan, scroll or w/s to zoom, arrows to orbit");
+    println!("p switches between preview and full shading, c prints the camera, escape quits\n");
+
+    // each pass is one sample per pixel, averaged as they come in
+    scene.prepare(&camera);
+    let mut camera = Camera { aa: 1, ..camera };
+    let full = match camera.integrator {
+        Integrator::Preview => Integrator::Path { min_depth: 3 },
+        other => other,
+    };
+    camera.integrator = Integrator::Preview;
+
+    let mut orbit = Orbit::new(&camera, &scene);
+    let mut total = vec![Vec3::new(0.0, 0.0, 0.0); width * height];
+    let mut buffer = vec![0; width * height];
+    let mut passes = 0;
+    let mut mouse: Option<(f32, f32)> = None;
+    let mut moved = true;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let now = window.get_mouse_pos(MouseMode::Discard);
+        if let (Some((x, y)), Some((px, py))) = (now, mouse) {
+            let (dx, dy) = ((x - px) as f64, (y - py) as f64);
+
+            if (dx != 0.0 || dy != 0.0) && window.get_mouse_down(MouseButton::Left) {
+                orbit.spin(-dx * DRAG_SPEED, -dy * DRAG_SPEED);
+                moved = true;
+            } else if (dx != 0.0 || dy != 0.0) && window.get_mouse_down(MouseButton::Right) {
+                orbit.pan(-dx / height as f64, dy / height as f64);
+                moved = true;
+            }
+        }
+        mouse = now;
+
+        if let Some((_, scroll)) = window.get_scroll_wheel().filter(|wheel| wheel.1 != 0.0) {
+            orbit.zoom((1.0 - ZOOM_SPEED).powf(scroll as f64));
+            moved = true;
+        }
+
+        let held = |key| window.is_key_down(key) as i32 as f64;
+        let yaw   = held(Key::Left) - held(Key::Right);
+        let pitch = held(Key::Up)   - held(Key::Down);
+        let zoom  = held(Key::S)    - held(Key::W);
+        if yaw != 0.0 || pitch != 0.0 || zoom != 0.0 {
+            orbit.spin(yaw * KEY_SPEED, pitch * KEY_SPEED);
+            orbit.zoom(1.0 + zoom * KEY_SPEED);
+            moved = true;
+        }
+
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            camera.integrator = match camera.integrator {
+                Integrator::Preview => full,
+                _ => Integrator::Preview,
+            };
+            moved = true;
+        }
+
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            print(&camera, &orbit);
+        }
+
+        if moved {
+            // a blocky look straight away, then start refining
+            orbit.apply(&mut camera);
+            for (value, color) in buffer.iter_mut().zip(pass(&scene, &camera, BLOCK).data()) {
+                *value = pack(*color);
+            }
+
+            total.iter_mut().for_each(|value| *value = Vec3::new(0.0, 0.0, 0.0));
+            passes = 0;
+            moved = false;
+        } else {
+            passes += 1;
+            for ((value, sum), color) in buffer.iter_mut().zip(total.iter_mut()).zip(pass(&scene, &camera, 1).data()) {
+                *sum = *sum + *color;
+                *value = pack(*sum / passes as f64);
+            }
+        }
+
+        if window.update_with_buffer(&buffer, width, height).is_err() { break; }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{Orbit, pass, BLOCK};
+    use crate::structures::vec3::Vec3;
+    use crate::structures::camera::Camera;
+    use crate::structures::scene::Scene;
+
+    fn orbit() -> Orbit {
+        Orbit {
+            target: Vec3::new(1.0, 0.0, 0.0),
+            offset: Vec3::new(0.0, 1.0, 4.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_orbit() {
+        let mut spun = orbit();
+        spun.spin(0.5, 0.2);
+        assert!((spun.offset.length() - orbit().offset.length()).abs() < 1e-9);
+        assert_eq!(spun.target, orbit().target);
+
+        // never goes over the top
+        spun.spin(0.0, 10.0);
+        assert!(spun.offset.unit().dot(&spun.up).abs() < 0.99);
+
+        let mut panned = orbit();
+        panned.pan(0.25, 0.0);
+        let moved = panned.target - orbit().target;
+        assert!(moved.dot(&orbit().offset).abs() < 1e-9);
+        assert!((moved.length() - 0.25 * orbit().offset.length()).abs() < 1e-9);
+
+        let mut zoomed = orbit();
+        zoomed.zoom(0.5);
+        assert!((zoomed.offset.length() - orbit().offset.length() * 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+            60.0, (16, 9), 1, 1, 1,
+        );
+
+        orbit().apply(&mut camera);
+        assert_eq!(camera.ray.origin, Vec3::new(1.0, 1.0, 4.0));
+        assert!((camera.ray.point_at(&orbit().offset.length()) - orbit().target).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_pass() {
+        let scene = Scene::empty();
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+            60.0, (20, 11), 1, 1, 1,
+        );
+
+        // every pixel in a block shares its corner's sample
+        let rough = pass(&scene, &camera, BLOCK);
+        assert_eq!((rough.width(), rough.height()), (20, 11));
+        for y in 0..11 {
+            for x in 0..20 {
+                assert_eq!(rough.get(x, y), rough.get(x / BLOCK * BLOCK, y / BLOCK * BLOCK));
+            }
+        }
+    }
+}
@@ -1,25 +1,104 @@
-use image::{ ImageBuffer, Rgb, ImageRgb8 };
-use std::path::Path;
-use std::io;
+use image::{ImageRgb8, Rgb};
+use image::hdr::HDREncoder;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::io::{self, BufWriter};
 
 use crate::structures::vec3::Vec3;
+use crate::structures::aov::{Aov, Pixel};
+use crate::structures::image::Image;
 
-pub fn png(image: Vec<Vec<Vec3>>, path: &Path) -> io::Result<()> {
-    // TODO: error handling
+pub fn png(image: &Image<Vec3>, path: &Path) -> io::Result<()> {
+    save(image, path, |pixel| pixel.colorize(1.0))
+}
+
+// colors already between 0 and 1, saved as they are
+pub fn colors(image: &Image<Vec3>, path: &Path) -> io::Result<()> {
+    save(image, path, bytes)
+}
+
+// linear radiance as a radiance .hdr file, with no tonemapping,
+// for comparing renders without losing what's past white
+pub fn hdr(image: &Image<Vec3>, path: &Path) -> io::Result<()> {
+    if image.is_empty() { return Err(empty()); }
+
+    let data: Vec<Rgb<f32>> = image.data().iter()
+        .map(|value| Rgb([value.x.max(0.0) as f32, value.y.max(0.0) as f32, value.z.max(0.0) as f32]))
+        .collect();
+    HDREncoder::new(BufWriter::new(File::create(path)?)).encode(&data, image.width(), image.height())?;
+
+    println!("Render saved to {}", path.display());
+    Ok(())
+}
+
+// writes every aov but the beauty pass next to path,
+// i.e. render.png becomes render.depth.png, render.normal.png, ...
+pub fn aovs(image: &Image<Pixel>, path: &Path) -> io::Result<()> {
+    for aov in Aov::ALL.iter().filter(|aov| **aov != Aov::Beauty) {
+        save(&image.layer(*aov), &beside(path, aov.name()), |value| display(*aov, value))?;
+    }
+
+    Ok(())
+}
 
-    // new buffer the width and height of the render
-    let mut buffer = ImageBuffer::new(
-        image[0].len() as u32,
-        image.len() as u32,
-    );
+fn beside(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("render");
+    // aovs are made to be looked at, so they're never hdr
+    let extension = match path.extension().and_then(|s| s.to_str()) {
+        None | Some("hdr") => "png",
+        Some(extension) => extension,
+    };
+    path.with_file_name(format!("{}.{}.{}", stem, name, extension))
+}
+
+fn empty() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "can't save an empty image")
+}
 
-    for (y, row) in image.iter().enumerate() {
-        for (x, pixel) in row.iter().enumerate() {
-            buffer.put_pixel(x as u32, y as u32, Rgb(pixel.colorize(1.0)));
-        }
+fn bytes(color: Vec3) -> [u8; 3] {
+    let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.9) as u8;
+    [channel(color.x), channel(color.y), channel(color.z)]
+}
+
+// map raw aov data to something viewable
+fn display(aov: Aov, value: Vec3) -> [u8; 3] {
+    match aov {
+        Aov::Beauty | Aov::Emission | Aov::Direct | Aov::Indirect => value.colorize(1.0),
+        // closer is brighter, the sky is black
+        Aov::Depth if value.x <= 0.0 => [0, 0, 0],
+        Aov::Depth => { let d = 1.0 / (1.0 + value.x); bytes(Vec3::new(d, d, d)) },
+        Aov::Normal => bytes((value + 1.0) * 0.5),
+        Aov::Albedo => bytes(value),
+        // scramble ids into distinct colors
+        Aov::Object | Aov::Material if value.x <= 0.0 => [0, 0, 0],
+        Aov::Object | Aov::Material => {
+            let hash = (value.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            [(hash >> 56) as u8, (hash >> 48) as u8, (hash >> 40) as u8]
+        },
     }
+}
 
-    ImageRgb8(buffer).save(&path)?;
+fn save(image: &Image<Vec3>, path: &Path, convert: impl Fn(Vec3) -> [u8; 3]) -> io::Result<()> {
+    if image.is_empty() { return Err(empty()); }
+
+    ImageRgb8(image.to_rgb8(convert)).save(&path)?;
     println!("Render saved to {}", path.display());
     return Ok(());
 }
+
+#[cfg(test)]
+pub mod test {
+    use std::env;
+
+    use super::png;
+    use crate::structures::vec3::Vec3;
+    use crate::structures::image::Image;
+
+    #[test]
+    fn test_empty() {
+        let path = env::temp_dir().join(format!("keikan-empty-{}.png", std::process::id()));
+        let error = png(&Image::new(0, 0, Vec3::new(0.0, 0.0, 0.0)), &path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}